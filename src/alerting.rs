@@ -0,0 +1,298 @@
+//! Alerting Module
+//!
+//! `webhook`'s `send_webhook` ships every parsed record to a single URL on every
+//! run, which is noisy when the operator only cares about failures. This module
+//! evaluates parsed records against configurable thresholds from [`Config`] and,
+//! only when one of them trips, builds a small structured [`AlertPayload`]
+//! (summary counts, offending source IPs, affected domains) and dispatches it —
+//! with retries and exponential backoff, like `webhook::WebhookHandler` — to one
+//! or more configured sink URLs.
+use crate::config::Config;
+use crate::models::{DkimVerdict, DmarcRecord, SpfVerdict};
+use anyhow::{Context, Result};
+use reqwest::{Client, Url};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// Thresholds an [`AlertEvaluator`] checks parsed records against, read from [`Config`].
+#[derive(Debug, Clone)]
+pub struct AlertRules {
+    /// Fire when the number of messages with both DKIM and SPF failing
+    /// (full DMARC failure, no alignment at all) exceeds this count.
+    pub full_failure_threshold: u32,
+    /// Fire for any `header_from` domain whose DMARC pass rate (aligned DKIM or
+    /// SPF pass) drops below this percentage (0-100).
+    pub min_pass_rate_percent: f64,
+}
+
+impl AlertRules {
+    /// Builds the rule set from the matching `DMARC_ALERT_*` configuration values.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            full_failure_threshold: config.alert_full_failure_threshold,
+            min_pass_rate_percent: config.alert_min_pass_rate_percent,
+        }
+    }
+}
+
+/// Which configured rule(s) caused an [`AlertPayload`] to be raised.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    FullFailureThresholdExceeded,
+    NewSourceIp,
+    PassRateBelowThreshold,
+}
+
+/// The structured summary sent to alert sinks, in place of a raw record dump.
+#[derive(Debug, Serialize, Clone)]
+pub struct AlertPayload {
+    pub kinds: Vec<AlertKind>,
+    pub total_records: usize,
+    pub full_failure_count: u32,
+    pub offending_source_ips: Vec<String>,
+    pub new_source_ips: Vec<String>,
+    pub affected_domains: Vec<String>,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Evaluates parsed DMARC records against a fixed [`AlertRules`] set.
+pub struct AlertEvaluator {
+    rules: AlertRules,
+}
+
+impl AlertEvaluator {
+    pub fn new(rules: AlertRules) -> Self {
+        Self { rules }
+    }
+
+    /// Evaluates `records`, returning `None` when no rule fires.
+    ///
+    /// `known_source_ips` is the set of source IPs already seen in prior reports
+    /// (persistence of that set is the caller's responsibility, e.g. a Redis set
+    /// kept by the worker); pass `None` to skip the new-source-IP rule entirely
+    /// when no such history is available.
+    pub fn evaluate(
+        &self,
+        records: &[DmarcRecord],
+        known_source_ips: Option<&HashSet<String>>,
+    ) -> Option<AlertPayload> {
+        if records.is_empty() {
+            return None;
+        }
+
+        let mut full_failure_count = 0u32;
+        let mut offending_ips = HashSet::new();
+        let mut new_ips = HashSet::new();
+        // (dmarc-aligned-pass count, total count) per `header_from` domain.
+        let mut domain_totals: HashMap<&str, (u32, u32)> = HashMap::new();
+
+        for record in records {
+            let is_full_failure =
+                record.policy_evaluated.dkim == DkimVerdict::Fail && record.policy_evaluated.spf == SpfVerdict::Fail;
+            if is_full_failure {
+                full_failure_count += record.count;
+                offending_ips.insert(record.source_ip.clone());
+            }
+
+            if let Some(known) = known_source_ips {
+                if !known.contains(&record.source_ip) {
+                    new_ips.insert(record.source_ip.clone());
+                }
+            }
+
+            let is_dmarc_pass =
+                record.policy_evaluated.dkim == DkimVerdict::Pass || record.policy_evaluated.spf == SpfVerdict::Pass;
+            let entry = domain_totals.entry(record.header_from.as_str()).or_insert((0, 0));
+            entry.1 += record.count;
+            if is_dmarc_pass {
+                entry.0 += record.count;
+            }
+        }
+
+        let mut kinds = Vec::new();
+        if full_failure_count > self.rules.full_failure_threshold {
+            kinds.push(AlertKind::FullFailureThresholdExceeded);
+        }
+        if !new_ips.is_empty() {
+            kinds.push(AlertKind::NewSourceIp);
+        }
+
+        let mut affected_domains: Vec<String> = domain_totals
+            .iter()
+            .filter(|(_, (pass, total))| {
+                *total > 0 && (*pass as f64 / *total as f64) * 100.0 < self.rules.min_pass_rate_percent
+            })
+            .map(|(domain, _)| domain.to_string())
+            .collect();
+        affected_domains.sort();
+        if !affected_domains.is_empty() {
+            kinds.push(AlertKind::PassRateBelowThreshold);
+        }
+
+        if kinds.is_empty() {
+            return None;
+        }
+
+        let mut offending_source_ips: Vec<String> = offending_ips.into_iter().collect();
+        offending_source_ips.sort();
+        let mut new_source_ips: Vec<String> = new_ips.into_iter().collect();
+        new_source_ips.sort();
+
+        Some(AlertPayload {
+            kinds,
+            total_records: records.len(),
+            full_failure_count,
+            offending_source_ips,
+            new_source_ips,
+            affected_domains,
+            timestamp: chrono::Utc::now(),
+        })
+    }
+}
+
+/// Sends an [`AlertPayload`] to one or more sink URLs, retrying each with
+/// exponential backoff the same way `webhook::WebhookHandler` does.
+pub struct AlertDispatcher {
+    client: Client,
+    sinks: Vec<Url>,
+    max_retries: u32,
+}
+
+impl AlertDispatcher {
+    /// Builds a dispatcher for `urls`. Returns an error if any URL is invalid.
+    pub fn new(urls: &[String], timeout: Duration, max_retries: u32) -> Result<Self> {
+        let sinks = urls
+            .iter()
+            .map(|url| Url::parse(url).with_context(|| format!("Invalid alert sink URL: {}", url)))
+            .collect::<Result<Vec<_>>>()?;
+        let client = Client::builder()
+            .timeout(timeout)
+            .build()
+            .context("Failed to create HTTP client for alert dispatch")?;
+        Ok(Self { client, sinks, max_retries })
+    }
+
+    /// Dispatches `payload` to every configured sink. A sink's delivery failure
+    /// is logged and does not stop delivery to the remaining sinks; this only
+    /// returns an error if every sink failed.
+    pub async fn dispatch(&self, payload: &AlertPayload) -> Result<()> {
+        let mut last_error = None;
+        let mut any_succeeded = false;
+
+        for url in &self.sinks {
+            match self.send_with_retry(url, payload).await {
+                Ok(()) => any_succeeded = true,
+                Err(e) => {
+                    log::warn!("Alert sink {} failed: {}", url, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        if any_succeeded || self.sinks.is_empty() {
+            Ok(())
+        } else {
+            Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No alert sinks configured")))
+        }
+    }
+
+    async fn send_with_retry(&self, url: &Url, payload: &AlertPayload) -> Result<()> {
+        let mut last_error = None;
+        for retry in 0..=self.max_retries {
+            if retry > 0 {
+                let delay = Duration::from_secs(2u64.pow(retry - 1));
+                log::info!("Retrying alert send to {} in {:?}...", url, delay);
+                sleep(delay).await;
+            }
+            match self.client.post(url.clone()).json(payload).send().await {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => {
+                    let status = response.status();
+                    let body = response.text().await.unwrap_or_else(|_| "Unable to read response body".to_string());
+                    last_error = Some(format!("HTTP {} - {}", status, body));
+                }
+                Err(e) => {
+                    last_error = Some(e.to_string());
+                }
+            }
+        }
+        Err(anyhow::anyhow!("Alert delivery to {} failed after {} attempts: {:?}", url, self.max_retries + 1, last_error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DateRange, PolicyEvaluated, SpfResult};
+
+    fn record(source_ip: &str, header_from: &str, dkim: DkimVerdict, spf: SpfVerdict, count: u32) -> DmarcRecord {
+        DmarcRecord {
+            source_ip: source_ip.to_string(),
+            count,
+            policy_evaluated: PolicyEvaluated { disposition: "none".to_string(), dkim: dkim.clone(), spf: spf.clone(), reasons: vec![] },
+            header_from: header_from.to_string(),
+            envelope_from: None,
+            envelope_to: None,
+            dkim: vec![],
+            spf: SpfResult { domain: header_from.to_string(), scope: "mfrom".to_string(), result: spf },
+            date_range: DateRange::default(),
+        }
+    }
+
+    #[test]
+    fn test_no_alert_when_within_thresholds() {
+        let evaluator = AlertEvaluator::new(AlertRules { full_failure_threshold: 10, min_pass_rate_percent: 50.0 });
+        let records = vec![record("203.0.113.1", "example.com", DkimVerdict::Pass, SpfVerdict::Pass, 100)];
+        assert!(evaluator.evaluate(&records, None).is_none());
+    }
+
+    #[test]
+    fn test_full_failure_threshold_triggers() {
+        let evaluator = AlertEvaluator::new(AlertRules { full_failure_threshold: 5, min_pass_rate_percent: 0.0 });
+        let records = vec![record("203.0.113.1", "example.com", DkimVerdict::Fail, SpfVerdict::Fail, 10)];
+        let payload = evaluator.evaluate(&records, None).unwrap();
+        assert!(payload.kinds.contains(&AlertKind::FullFailureThresholdExceeded));
+        assert_eq!(payload.full_failure_count, 10);
+        assert_eq!(payload.offending_source_ips, vec!["203.0.113.1".to_string()]);
+    }
+
+    #[test]
+    fn test_pass_rate_below_threshold_triggers() {
+        let evaluator = AlertEvaluator::new(AlertRules { full_failure_threshold: 1000, min_pass_rate_percent: 90.0 });
+        let records = vec![
+            record("203.0.113.1", "example.com", DkimVerdict::Pass, SpfVerdict::Fail, 5),
+            record("203.0.113.2", "example.com", DkimVerdict::Fail, SpfVerdict::Fail, 95),
+        ];
+        let payload = evaluator.evaluate(&records, None).unwrap();
+        assert!(payload.kinds.contains(&AlertKind::PassRateBelowThreshold));
+        assert_eq!(payload.affected_domains, vec!["example.com".to_string()]);
+    }
+
+    #[test]
+    fn test_new_source_ip_triggers() {
+        let evaluator = AlertEvaluator::new(AlertRules { full_failure_threshold: 1000, min_pass_rate_percent: 0.0 });
+        let records = vec![record("203.0.113.9", "example.com", DkimVerdict::Pass, SpfVerdict::Pass, 1)];
+        let known: HashSet<String> = HashSet::new();
+        let payload = evaluator.evaluate(&records, Some(&known)).unwrap();
+        assert!(payload.kinds.contains(&AlertKind::NewSourceIp));
+        assert_eq!(payload.new_source_ips, vec!["203.0.113.9".to_string()]);
+    }
+
+    #[test]
+    fn test_known_source_ip_does_not_trigger() {
+        let evaluator = AlertEvaluator::new(AlertRules { full_failure_threshold: 1000, min_pass_rate_percent: 0.0 });
+        let records = vec![record("203.0.113.9", "example.com", DkimVerdict::Pass, SpfVerdict::Pass, 1)];
+        let mut known: HashSet<String> = HashSet::new();
+        known.insert("203.0.113.9".to_string());
+        assert!(evaluator.evaluate(&records, Some(&known)).is_none());
+    }
+
+    #[test]
+    fn test_invalid_sink_url_rejected() {
+        let result = AlertDispatcher::new(&["not a url".to_string()], Duration::from_secs(5), 3);
+        assert!(result.is_err());
+    }
+}
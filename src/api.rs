@@ -0,0 +1,371 @@
+//! HTTP Ingestion API
+//!
+//! Exposes a small `axum`-based HTTP server so mail gateways can submit DMARC reports
+//! over the network instead of only through the RabbitMQ worker's local file path.
+//! `POST /reports` accepts a raw XML, gzip, or ZIP report body, enforces the same
+//! security limits as the CLI and worker (`Config::max_file_size` and the rest, via
+//! `extract_zip`), enqueues a `TaskMessage` onto the `dmarc_processing` queue for the
+//! worker to persist, and returns the parsed record count as JSON. Responses are
+//! compressed with gzip or deflate when the client advertises support via
+//! `Accept-Encoding`, which matters once aggregate report summaries grow large.
+//!
+//! `POST /reports/batch` accepts `multipart/form-data` instead, for gateways that
+//! want to submit several attachments in one request. It parses the body with the
+//! `multer` crate directly off the streaming request, handling one part at a time
+//! and returning a JSON array of per-file outcomes so one bad attachment doesn't
+//! fail the rest.
+//!
+//! Both endpoints parse XML through `dmarcer::cache`'s content-addressed report
+//! cache, so a mail provider re-sending the exact same report is cheap to ingest
+//! the second time.
+
+use anyhow::{Context, Result};
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Router,
+};
+use flate2::{
+    write::{DeflateEncoder, GzEncoder},
+    Compression,
+};
+use lapin::{
+    options::{BasicPublishOptions, QueueDeclareOptions},
+    types::FieldTable,
+    BasicProperties, Connection, ConnectionProperties,
+};
+use multer::Multipart;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use dmarcer::{extract_zip, parse_dmarc_xml_cached, Config};
+
+/// Shared state handed to every request handler.
+#[derive(Clone)]
+struct ApiState {
+    config: Arc<Config>,
+    amqp_channel: Arc<lapin::Channel>,
+}
+
+/// Message format published onto the `dmarc_processing` queue, matching the worker's.
+#[derive(Debug, Serialize, Deserialize)]
+struct TaskMessage {
+    task_id: String,
+    file_path: String,
+    organization_id: String,
+    domain: String,
+    original_filename: String,
+}
+
+#[derive(Debug, Serialize)]
+struct IngestResponse {
+    task_id: String,
+    record_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+/// Outcome of one attachment within a `/reports/batch` submission. A bad part
+/// (unparseable, too large, truncated) becomes an `Error` entry rather than
+/// failing the whole request.
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+enum BatchFileResult {
+    Ok { filename: String, record_count: usize },
+    Error { filename: String, error: String },
+}
+
+#[derive(Debug, Serialize)]
+struct BatchResponse {
+    results: Vec<BatchFileResult>,
+}
+
+/// Handles `POST /reports`: stages the body to disk, extracts/parses it under the
+/// shared `Config`'s security limits, and enqueues a task for the worker.
+async fn ingest_report(State(state): State<ApiState>, headers: HeaderMap, body: Bytes) -> Response {
+    if body.len() > state.config.max_file_size {
+        return error_response(StatusCode::PAYLOAD_TOO_LARGE, "Request body exceeds max_file_size", &headers);
+    }
+
+    let task_id = Uuid::new_v4().to_string();
+    let ext = staged_extension(None, &body);
+    let temp_path = std::env::temp_dir().join(format!("dmarcer-upload-{}.{}", task_id, ext));
+    if let Err(e) = tokio::fs::write(&temp_path, &body).await {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, &format!("Failed to stage upload: {}", e), &headers);
+    }
+
+    let record_count = match extract_and_count(&state.config, &temp_path).await {
+        Ok(count) => count,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return error_response(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string(), &headers);
+        }
+    };
+
+    let task_msg = TaskMessage {
+        task_id: task_id.clone(),
+        file_path: temp_path.to_string_lossy().to_string(),
+        organization_id: String::new(),
+        domain: String::new(),
+        original_filename: String::new(),
+    };
+    if let Err(e) = publish_task(&state.amqp_channel, &task_msg).await {
+        return error_response(StatusCode::INTERNAL_SERVER_ERROR, &format!("Failed to enqueue task: {}", e), &headers);
+    }
+
+    encode_response(StatusCode::ACCEPTED, &IngestResponse { task_id, record_count }, &headers)
+}
+
+/// Runs the async `extract_zip`/`parse_dmarc_xml` pipeline on a staged upload and
+/// returns the total number of records it contains. Parsing goes through the
+/// content-addressed report cache, so a mail provider re-sending the same report
+/// is cheap the second time around.
+async fn extract_and_count(config: &Arc<Config>, path: &std::path::Path) -> Result<usize> {
+    let extracted = extract_zip(path, config).await?;
+
+    let mut count = 0;
+    for xml in &extracted {
+        let (records, _policy, _metadata) =
+            parse_dmarc_xml_cached(xml, config).context("Failed to parse DMARC XML")?;
+        count += records.len();
+    }
+    Ok(count)
+}
+
+/// Picks the extension a staged upload should be saved under, since an HTTP
+/// upload has no filesystem extension of its own and `extract_zip` still reads
+/// the file back off disk by path. `extract_zip` sniffs the real magic bytes
+/// itself, so this only needs to produce *a* plausible extension: prefers
+/// `filename`'s own extension, then falls back to sniffing `body`'s leading
+/// magic bytes, and finally assumes a bare XML report, since `extract_zip`
+/// only accepts a file without recognized magic bytes when its extension is
+/// `.xml`.
+fn staged_extension(filename: Option<&str>, body: &[u8]) -> &'static str {
+    const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+    const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+
+    let named_ext = filename
+        .and_then(|name| std::path::Path::new(name).extension())
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase());
+    match named_ext.as_deref() {
+        Some("zip") => return "zip",
+        Some("gz") => return "gz",
+        Some("xml") => return "xml",
+        _ => {}
+    }
+
+    if body.starts_with(ZIP_MAGIC) {
+        "zip"
+    } else if body.starts_with(GZIP_MAGIC) {
+        "gz"
+    } else {
+        "xml"
+    }
+}
+
+/// Handles `POST /reports/batch`: parses a `multipart/form-data` body with
+/// `multer`, reading directly off the streaming request body instead of
+/// buffering it whole the way `/reports` buffers a single-file body into
+/// `Bytes`. Parts are handled one at a time, so only one attachment's bytes
+/// are ever resident at once no matter how many the request carries, and a
+/// part count over `config.max_files_in_zip` stops the batch early rather
+/// than unboundedly accepting more. A single part failing to stage or parse
+/// becomes an `Error` entry in the response array; it does not abort the
+/// rest of the batch.
+async fn ingest_batch(State(state): State<ApiState>, headers: HeaderMap, body: Body) -> Response {
+    let content_type = headers.get("content-type").and_then(|v| v.to_str().ok()).unwrap_or("");
+    let boundary = match multer::parse_boundary(content_type) {
+        Ok(boundary) => boundary,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, &format!("Not a multipart request: {}", e), &headers),
+    };
+
+    let mut multipart = Multipart::new(body.into_data_stream(), boundary);
+    let mut results = Vec::new();
+
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                results.push(BatchFileResult::Error {
+                    filename: "unknown".to_string(),
+                    error: format!("Malformed multipart part: {}", e),
+                });
+                break;
+            }
+        };
+
+        if results.len() >= state.config.max_files_in_zip {
+            results.push(BatchFileResult::Error {
+                filename: field.file_name().unwrap_or("unknown").to_string(),
+                error: "Too many files in batch request".to_string(),
+            });
+            break;
+        }
+
+        let filename = field.file_name().unwrap_or("upload").to_string();
+        let result = process_batch_field(&state.config, filename.clone(), field)
+            .await
+            .unwrap_or_else(|e| BatchFileResult::Error { filename, error: e.to_string() });
+        results.push(result);
+    }
+
+    encode_response(StatusCode::OK, &BatchResponse { results }, &headers)
+}
+
+/// Streams one multipart field to a short-lived temp file chunk by chunk,
+/// rejecting it once it crosses `config.max_file_size` without ever holding
+/// the whole part in memory, then runs it through the same extract/parse
+/// pipeline as a single-report upload and removes the temp file again.
+///
+/// The temp file's extension is picked via [`staged_extension`] from `filename`
+/// (or, failing that, the first chunk's magic bytes) before anything is
+/// written, since `extract_zip` reads the file back off disk by path; the
+/// part's own filename is otherwise only used to label the response.
+async fn process_batch_field(
+    config: &Arc<Config>,
+    filename: String,
+    mut field: multer::Field<'_>,
+) -> Result<BatchFileResult> {
+    let first_chunk = field.chunk().await.context("Failed to read multipart chunk")?;
+    let ext = staged_extension(Some(&filename), first_chunk.as_deref().unwrap_or(&[]));
+    let temp_path = std::env::temp_dir().join(format!("dmarcer-batch-{}.{}", Uuid::new_v4(), ext));
+    {
+        let mut file = tokio::fs::File::create(&temp_path)
+            .await
+            .context("Failed to create temp file for batch part")?;
+        let mut total: usize = 0;
+        if let Some(chunk) = first_chunk {
+            total += chunk.len();
+            if total > config.max_file_size {
+                drop(file);
+                let _ = tokio::fs::remove_file(&temp_path).await;
+                return Err(anyhow::anyhow!("Part exceeds max_file_size"));
+            }
+            file.write_all(&chunk).await.context("Failed to stage multipart chunk")?;
+        }
+        while let Some(chunk) = field.chunk().await.context("Failed to read multipart chunk")? {
+            total += chunk.len();
+            if total > config.max_file_size {
+                drop(file);
+                let _ = tokio::fs::remove_file(&temp_path).await;
+                return Err(anyhow::anyhow!("Part exceeds max_file_size"));
+            }
+            file.write_all(&chunk).await.context("Failed to stage multipart chunk")?;
+        }
+    }
+
+    let outcome = extract_and_count(config, &temp_path).await;
+    let _ = tokio::fs::remove_file(&temp_path).await;
+
+    Ok(match outcome {
+        Ok(record_count) => BatchFileResult::Ok { filename, record_count },
+        Err(e) => BatchFileResult::Error { filename, error: e.to_string() },
+    })
+}
+
+/// Declares the queue (idempotent) and publishes `task_msg` onto it.
+async fn publish_task(channel: &lapin::Channel, task_msg: &TaskMessage) -> Result<()> {
+    channel
+        .queue_declare("dmarc_processing", QueueDeclareOptions::default(), FieldTable::default())
+        .await?;
+    let payload = serde_json::to_vec(task_msg)?;
+    channel
+        .basic_publish("", "dmarc_processing", BasicPublishOptions::default(), &payload, BasicProperties::default())
+        .await?
+        .await?;
+    Ok(())
+}
+
+/// Serializes `body` as JSON and hands it to [`compress_for_client`] for optional
+/// gzip/deflate compression based on the request's `Accept-Encoding` header.
+fn encode_response<T: Serialize>(status: StatusCode, body: &T, headers: &HeaderMap) -> Response {
+    match serde_json::to_vec(body) {
+        Ok(json) => compress_for_client(status, json, headers),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to serialize response: {}", e)).into_response(),
+    }
+}
+
+fn error_response(status: StatusCode, message: &str, headers: &HeaderMap) -> Response {
+    encode_response(status, &ErrorResponse { error: message.to_string() }, headers)
+}
+
+/// Compresses `json` with gzip or deflate when the client's `Accept-Encoding` asks for
+/// it (gzip preferred), setting `Content-Encoding` accordingly; otherwise returns it as-is.
+fn compress_for_client(status: StatusCode, json: Vec<u8>, headers: &HeaderMap) -> Response {
+    let accept_encoding = headers
+        .get("accept-encoding")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+
+    let encoded = if accept_encoding.contains("gzip") {
+        gzip_compress(&json).map(|bytes| ("gzip", bytes))
+    } else if accept_encoding.contains("deflate") {
+        deflate_compress(&json).map(|bytes| ("deflate", bytes))
+    } else {
+        None
+    };
+
+    let mut response = match encoded {
+        Some((encoding, compressed)) => {
+            let mut response = (status, compressed).into_response();
+            response
+                .headers_mut()
+                .insert("content-encoding", HeaderValue::from_static(encoding));
+            response
+        }
+        None => (status, json).into_response(),
+    };
+    response
+        .headers_mut()
+        .insert("content-type", HeaderValue::from_static("application/json"));
+    response
+}
+
+fn gzip_compress(data: &[u8]) -> Option<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).ok()?;
+    encoder.finish().ok()
+}
+
+fn deflate_compress(data: &[u8]) -> Option<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).ok()?;
+    encoder.finish().ok()
+}
+
+fn router(state: ApiState) -> Router {
+    Router::new()
+        .route("/reports", post(ingest_report))
+        .route("/reports/batch", post(ingest_batch))
+        .with_state(state)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+
+    let config = Arc::new(Config::new().context("Failed to load configuration")?);
+
+    let amqp_addr = std::env::var("AMQP_ADDR").unwrap_or_else(|_| "amqp://127.0.0.1:5672/%2f".into());
+    let conn = Connection::connect(&amqp_addr, ConnectionProperties::default()).await?;
+    let amqp_channel = Arc::new(conn.create_channel().await?);
+
+    let bind_addr = std::env::var("DMARC_API_BIND").unwrap_or_else(|_| "0.0.0.0:8080".into());
+    let app = router(ApiState { config, amqp_channel });
+
+    log::info!("DMARCer ingestion API listening on {}", bind_addr);
+    let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
+    axum::serve(listener, app).await?;
+    Ok(())
+}
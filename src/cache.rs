@@ -0,0 +1,234 @@
+//! Report Cache Module
+//!
+//! Mail providers frequently re-send (or a gateway double-submits) the exact same
+//! aggregate report, so this module content-addresses parsed results by the SHA-256
+//! hash of the raw report bytes and skips `parse_dmarc_xml` entirely on a repeat.
+//! A bounded in-memory `quick_cache` layer is checked first; entries optionally also
+//! persist as JSON files under a configured directory, so a report seen once by one
+//! process (say, the worker) is still a cache hit in another (the CLI). Entries older
+//! than a configurable max-age are treated as misses, the same TTL-based freshness
+//! convention [`crate::geo`] uses for geolocation lookups.
+//!
+//! `parse_dmarc_xml` actually returns `(records, policy, metadata)`, but a cache entry
+//! only ever stores the `records`/`policy` pair, so a cache hit's metadata is always
+//! `None` rather than a stale or fabricated value. Callers that display metadata (the
+//! CLI's table output) simply show nothing for a deduplicated report, the same way
+//! they already do when a report omits its `<report_metadata>` block.
+use crate::config::Config;
+use crate::error::Result;
+use crate::models::{DmarcPolicy, DmarcRecord, ReportMetadata};
+use lazy_static::lazy_static;
+use quick_cache::sync::Cache;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A cached parse result, keyed by the SHA-256 hash of the raw report bytes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedReport {
+    records: Vec<DmarcRecord>,
+    policy: DmarcPolicy,
+    /// Unix timestamp (seconds) the entry was stored, used for max-age eviction.
+    cached_at: u64,
+}
+
+lazy_static! {
+    static ref CACHE: Mutex<Option<Cache<String, CachedReport>>> = Mutex::new(None);
+}
+
+/// Lazily builds the in-memory cache sized to `config.report_cache_size` on first
+/// use. `quick_cache::sync::Cache` fixes its capacity at construction, so unlike the
+/// LRU in [`crate::geo`] it isn't resized on a later config reload -- only the first
+/// observed size takes effect for the lifetime of the process.
+fn with_cache<R>(config: &Config, f: impl FnOnce(&Cache<String, CachedReport>) -> R) -> R {
+    let mut guard = CACHE.lock().unwrap();
+    let cache = guard.get_or_insert_with(|| Cache::new(config.report_cache_size.max(1)));
+    f(cache)
+}
+
+/// Parses `xml_content` into `(records, policy, metadata)`, transparently caching the
+/// `(records, policy)` pair under the SHA-256 hash of `xml_content`'s raw bytes.
+///
+/// On a cache hit (in memory, or on disk when `config.report_cache_dir` is set),
+/// `parse_dmarc_xml` is skipped entirely and `metadata` comes back `None`, since a
+/// cache entry never stores it. On a miss, the report is parsed normally, the result
+/// is stored (subject to later eviction once `config.report_cache_max_age_secs`
+/// elapses), and the full parse -- metadata included -- is returned.
+pub fn parse_dmarc_xml_cached(
+    xml_content: &str,
+    config: &Config,
+) -> Result<(Vec<DmarcRecord>, DmarcPolicy, Option<ReportMetadata>)> {
+    let key = hash_bytes(xml_content.as_bytes());
+
+    if let Some(cached) = get_fresh(&key, config) {
+        return Ok((cached.records, cached.policy, None));
+    }
+
+    let (records, policy, metadata) = crate::xml_parser::parse_dmarc_xml(xml_content)?;
+    let entry = CachedReport {
+        records: records.clone(),
+        policy: policy.clone(),
+        cached_at: now_secs(),
+    };
+    with_cache(config, |cache| cache.insert(key.clone(), entry.clone()));
+    write_disk(config, &key, &entry);
+
+    Ok((records, policy, Some(metadata)))
+}
+
+/// Returns the cached entry for `key` if present (checking memory, then disk) and not
+/// older than `config.report_cache_max_age_secs`; a stale in-memory entry is evicted
+/// and treated as a miss, and a fresh disk entry is promoted back into memory.
+fn get_fresh(key: &str, config: &Config) -> Option<CachedReport> {
+    if let Some(entry) = with_cache(config, |cache| cache.get(key)) {
+        if is_fresh(entry.cached_at, config.report_cache_max_age_secs) {
+            return Some(entry);
+        }
+        with_cache(config, |cache| cache.remove(key));
+    }
+
+    let entry = read_disk(config, key)?;
+    if !is_fresh(entry.cached_at, config.report_cache_max_age_secs) {
+        return None;
+    }
+    with_cache(config, |cache| cache.insert(key.to_string(), entry.clone()));
+    Some(entry)
+}
+
+fn hash_bytes(bytes: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+    hex::encode(hasher.finalize())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn is_fresh(cached_at: u64, max_age_secs: u64) -> bool {
+    now_secs().saturating_sub(cached_at) < max_age_secs
+}
+
+fn disk_path(config: &Config, key: &str) -> Option<PathBuf> {
+    config.report_cache_dir.as_ref().map(|dir| PathBuf::from(dir).join(format!("{}.json", key)))
+}
+
+/// Reads a persisted entry from disk, if a cache directory is configured and the file
+/// exists and parses. Any failure (missing directory, corrupt JSON) is treated as a
+/// miss rather than propagated, since a broken cache file should never fail processing
+/// of a report that would otherwise parse fine.
+fn read_disk(config: &Config, key: &str) -> Option<CachedReport> {
+    let path = disk_path(config, key)?;
+    let contents = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Persists `entry` to disk under the configured cache directory, creating it if
+/// needed. Failures are logged and otherwise ignored: disk persistence is a
+/// best-effort optimization, not a durability guarantee.
+fn write_disk(config: &Config, key: &str, entry: &CachedReport) {
+    let Some(path) = disk_path(config, key) else {
+        return;
+    };
+    if let Some(parent) = path.parent() {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            log::warn!("Failed to create report cache directory {}: {}", parent.display(), e);
+            return;
+        }
+    }
+    let json = match serde_json::to_string(entry) {
+        Ok(json) => json,
+        Err(e) => {
+            log::warn!("Failed to serialize report cache entry: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = std::fs::write(&path, json) {
+        log::warn!("Failed to write report cache entry {}: {}", path.display(), e);
+    }
+}
+
+/// Clears the in-memory report cache. Does not touch any on-disk entries.
+#[allow(dead_code)]
+pub fn clear_cache() {
+    if let Some(cache) = CACHE.lock().unwrap().as_ref() {
+        cache.clear();
+    }
+}
+
+/// Returns the current number of entries in the in-memory report cache.
+#[allow(dead_code)]
+pub fn cache_size() -> usize {
+    CACHE.lock().unwrap().as_ref().map(|cache| cache.len()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config(max_age_secs: u64, dir: Option<String>) -> Config {
+        let mut config = Config::new().unwrap();
+        config.report_cache_max_age_secs = max_age_secs;
+        config.report_cache_dir = dir;
+        config
+    }
+
+    const SAMPLE_XML: &str = r#"
+    <feedback>
+        <record>
+            <source_ip>1.2.3.4</source_ip>
+            <count>1</count>
+            <header_from>example.com</header_from>
+        </record>
+    </feedback>
+    "#;
+
+    #[test]
+    fn test_cache_hit_skips_reparse_and_drops_metadata() {
+        clear_cache();
+        let config = test_config(3600, None);
+
+        let (first_records, first_policy, metadata) = parse_dmarc_xml_cached(SAMPLE_XML, &config).unwrap();
+        assert!(metadata.is_some());
+
+        let (records, policy, metadata) = parse_dmarc_xml_cached(SAMPLE_XML, &config).unwrap();
+        assert_eq!(records.len(), first_records.len());
+        assert_eq!(policy, first_policy);
+        assert!(metadata.is_none());
+        clear_cache();
+    }
+
+    #[test]
+    fn test_cache_expiry_treated_as_miss() {
+        clear_cache();
+        let config = test_config(0, None);
+
+        let (_records, _policy, metadata) = parse_dmarc_xml_cached(SAMPLE_XML, &config).unwrap();
+        assert!(metadata.is_some());
+
+        // A max-age of zero means the entry is immediately stale, so the second
+        // call re-parses instead of hitting the cache.
+        let (_records, _policy, metadata) = parse_dmarc_xml_cached(SAMPLE_XML, &config).unwrap();
+        assert!(metadata.is_some());
+        clear_cache();
+    }
+
+    #[test]
+    fn test_disk_cache_survives_memory_eviction() {
+        clear_cache();
+        let dir = std::env::temp_dir().join(format!("dmarcer-report-cache-test-{}", std::process::id()));
+        let config = test_config(3600, Some(dir.to_string_lossy().to_string()));
+
+        parse_dmarc_xml_cached(SAMPLE_XML, &config).unwrap();
+        clear_cache(); // Drop the in-memory entry; only the disk copy remains.
+
+        let (records, _policy, metadata) = parse_dmarc_xml_cached(SAMPLE_XML, &config).unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(metadata.is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+        clear_cache();
+    }
+}
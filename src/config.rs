@@ -7,16 +7,68 @@
 use anyhow::Result;
 use std::env;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Config {
     pub webhook_url: Option<String>,
     #[allow(dead_code)]
     pub webhook_timeout: u64,
+    /// Selects the outgoing webhook [`Authenticator`](crate::webhook::Authenticator):
+    /// `"none"` (default), `"bearer"`, `"header"`, or `"hmac"`.
+    pub webhook_auth_mode: String,
+    /// Bearer token (`bearer` mode) or shared secret (`hmac` mode).
+    pub webhook_auth_token: Option<String>,
+    /// Header name to send (`header` mode).
+    pub webhook_auth_header_name: Option<String>,
+    /// Header value to send (`header` mode).
+    pub webhook_auth_header_value: Option<String>,
+    /// Name of the signature header written by `hmac` mode.
+    pub webhook_hmac_signature_header: String,
+    /// Selects outgoing webhook payload compression: `"disabled"` (default),
+    /// `"gzip"`, or `"deflate"`.
+    pub webhook_compression: String,
+    /// Minimum serialized payload size, in bytes, before compression is applied.
+    pub webhook_compression_threshold_bytes: usize,
     pub max_file_size: usize,
     pub max_decompressed_size: usize,
     pub max_files_in_zip: usize,
     pub max_compression_ratio: f64,
     pub max_filename_length: usize,
+    /// Path to a GeoLite2/GeoIP2 City `.mmdb` file for offline geolocation.
+    /// When unset, `GeoLookup` falls back to the online IP-API service.
+    pub mmdb_path: Option<String>,
+    /// Path to a GeoLite2/GeoIP2 ASN `.mmdb` file used to fill `asn`/`organization`.
+    pub mmdb_asn_path: Option<String>,
+    /// Maximum number of entries retained in the geolocation LRU cache.
+    pub geo_cache_size: usize,
+    /// Number of seconds a cached geolocation entry remains valid before it is treated as a miss.
+    pub geo_cache_ttl_secs: u64,
+    /// Maximum number of outbound IP-API requests per minute (token-bucket rate limit).
+    pub geo_rate_per_min: u32,
+    /// IMAP server hostname for mailbox ingestion. When unset, `--mailbox` refuses to run.
+    pub imap_host: Option<String>,
+    /// IMAP port; 993 (implicit TLS) by default.
+    pub imap_port: u16,
+    pub imap_username: Option<String>,
+    pub imap_password: Option<String>,
+    /// Mailbox folder to scan for unread DMARC report attachments.
+    pub imap_folder: String,
+    /// Sink URLs for the alerting subsystem (see `alerting`). May name more than
+    /// one endpoint; empty disables alerting entirely.
+    pub alert_webhook_urls: Vec<String>,
+    /// Fire an alert when the count of full-DMARC-failure messages (DKIM and SPF
+    /// both failing) exceeds this threshold.
+    pub alert_full_failure_threshold: u32,
+    /// Fire an alert when a domain's DMARC pass rate drops below this percentage.
+    pub alert_min_pass_rate_percent: f64,
+    pub alert_timeout_secs: u64,
+    pub alert_max_retries: u32,
+    /// Maximum number of entries retained in the in-memory report cache (see `cache`).
+    pub report_cache_size: usize,
+    /// Number of seconds a cached parsed report remains valid before it is treated as a miss.
+    pub report_cache_max_age_secs: u64,
+    /// Directory to persist cached report entries under as JSON files. When unset, the
+    /// report cache is in-memory only and does not survive a process restart.
+    pub report_cache_dir: Option<String>,
 }
 
 impl Config {
@@ -66,6 +118,129 @@ impl Config {
             .ok()
             .filter(|s| !s.is_empty());
 
+        let mmdb_path = env::var("DMARC_MMDB_PATH")
+            .map(|s| s.trim().to_string())
+            .ok()
+            .filter(|s| !s.is_empty());
+
+        let mmdb_asn_path = env::var("DMARC_MMDB_ASN_PATH")
+            .map(|s| s.trim().to_string())
+            .ok()
+            .filter(|s| !s.is_empty());
+
+        let geo_cache_size = env::var("DMARC_GEO_CACHE_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(10_000);
+
+        let geo_cache_ttl_secs = env::var("DMARC_GEO_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(3600);
+
+        let geo_rate_per_min = env::var("DMARC_GEO_RATE_PER_MIN")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(45);
+
+        let imap_host = env::var("DMARC_IMAP_HOST")
+            .map(|s| s.trim().to_string())
+            .ok()
+            .filter(|s| !s.is_empty());
+
+        let imap_port = env::var("DMARC_IMAP_PORT")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(993);
+
+        let imap_username = env::var("DMARC_IMAP_USERNAME")
+            .map(|s| s.trim().to_string())
+            .ok()
+            .filter(|s| !s.is_empty());
+
+        let imap_password = env::var("DMARC_IMAP_PASSWORD").ok().filter(|s| !s.is_empty());
+
+        let imap_folder = env::var("DMARC_IMAP_FOLDER")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "INBOX".to_string());
+
+        let alert_webhook_urls = env::var("DMARC_ALERT_WEBHOOK_URLS")
+            .ok()
+            .map(|s| {
+                s.split(',')
+                    .map(|url| url.trim().to_string())
+                    .filter(|url| !url.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let alert_full_failure_threshold = env::var("DMARC_ALERT_FULL_FAILURE_THRESHOLD")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(10);
+
+        let alert_min_pass_rate_percent = env::var("DMARC_ALERT_MIN_PASS_RATE_PERCENT")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(80.0);
+
+        let alert_timeout_secs = env::var("DMARC_ALERT_TIMEOUT_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(30);
+
+        let alert_max_retries = env::var("DMARC_ALERT_MAX_RETRIES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(3);
+
+        let report_cache_size = env::var("DMARC_REPORT_CACHE_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1_000);
+
+        let report_cache_max_age_secs = env::var("DMARC_REPORT_CACHE_MAX_AGE_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(86_400);
+
+        let report_cache_dir = env::var("DMARC_REPORT_CACHE_DIR")
+            .map(|s| s.trim().to_string())
+            .ok()
+            .filter(|s| !s.is_empty());
+
+        let webhook_auth_mode = env::var("DMARC_WEBHOOK_AUTH_MODE")
+            .ok()
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "none".to_string());
+
+        let webhook_auth_token = env::var("DMARC_WEBHOOK_AUTH_TOKEN").ok().filter(|s| !s.is_empty());
+
+        let webhook_auth_header_name = env::var("DMARC_WEBHOOK_AUTH_HEADER_NAME")
+            .map(|s| s.trim().to_string())
+            .ok()
+            .filter(|s| !s.is_empty());
+
+        let webhook_auth_header_value = env::var("DMARC_WEBHOOK_AUTH_HEADER_VALUE").ok().filter(|s| !s.is_empty());
+
+        let webhook_hmac_signature_header = env::var("DMARC_WEBHOOK_HMAC_SIGNATURE_HEADER")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "X-DMARCer-Signature".to_string());
+
+        let webhook_compression = env::var("DMARC_WEBHOOK_COMPRESSION")
+            .ok()
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "disabled".to_string());
+
+        let webhook_compression_threshold_bytes = env::var("DMARC_WEBHOOK_COMPRESSION_THRESHOLD_BYTES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1024);
+
         Ok(Config {
             webhook_url,
             webhook_timeout,
@@ -74,8 +249,58 @@ impl Config {
             max_files_in_zip,
             max_compression_ratio,
             max_filename_length,
+            mmdb_path,
+            mmdb_asn_path,
+            geo_cache_size,
+            geo_cache_ttl_secs,
+            geo_rate_per_min,
+            imap_host,
+            imap_port,
+            imap_username,
+            imap_password,
+            imap_folder,
+            alert_webhook_urls,
+            alert_full_failure_threshold,
+            alert_min_pass_rate_percent,
+            alert_timeout_secs,
+            alert_max_retries,
+            report_cache_size,
+            report_cache_max_age_secs,
+            report_cache_dir,
+            webhook_auth_mode,
+            webhook_auth_token,
+            webhook_auth_header_name,
+            webhook_auth_header_value,
+            webhook_hmac_signature_header,
+            webhook_compression,
+            webhook_compression_threshold_bytes,
         })
     }
+
+    /// Re-reads configuration from the environment, optionally overlaying simple
+    /// `KEY=VALUE` lines from `config_file` first (later lines win; blank lines and
+    /// lines starting with `#` are ignored).
+    ///
+    /// Used for hot-reloading a long-lived worker: this reuses the exact same
+    /// validation as [`Config::new`] (the 500MB cap, etc.), so a bad reload returns
+    /// an error and the caller can keep running with the previously active config.
+    pub fn reload(config_file: Option<&str>) -> Result<Self> {
+        if let Some(path) = config_file {
+            for line in std::fs::read_to_string(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read config file {}: {}", path, e))?
+                .lines()
+            {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                if let Some((key, value)) = line.split_once('=') {
+                    env::set_var(key.trim(), value.trim());
+                }
+            }
+        }
+        Self::new()
+    }
 }
 
 #[cfg(test)]
@@ -93,6 +318,31 @@ mod tests {
         env::remove_var("DMARC_MAX_FILES_IN_ZIP");
         env::remove_var("DMARC_MAX_COMPRESSION_RATIO");
         env::remove_var("DMARC_MAX_FILENAME_LENGTH");
+        env::remove_var("DMARC_MMDB_PATH");
+        env::remove_var("DMARC_MMDB_ASN_PATH");
+        env::remove_var("DMARC_GEO_CACHE_SIZE");
+        env::remove_var("DMARC_GEO_CACHE_TTL_SECS");
+        env::remove_var("DMARC_GEO_RATE_PER_MIN");
+        env::remove_var("DMARC_IMAP_HOST");
+        env::remove_var("DMARC_IMAP_PORT");
+        env::remove_var("DMARC_IMAP_USERNAME");
+        env::remove_var("DMARC_IMAP_PASSWORD");
+        env::remove_var("DMARC_IMAP_FOLDER");
+        env::remove_var("DMARC_ALERT_WEBHOOK_URLS");
+        env::remove_var("DMARC_ALERT_FULL_FAILURE_THRESHOLD");
+        env::remove_var("DMARC_ALERT_MIN_PASS_RATE_PERCENT");
+        env::remove_var("DMARC_ALERT_TIMEOUT_SECS");
+        env::remove_var("DMARC_ALERT_MAX_RETRIES");
+        env::remove_var("DMARC_REPORT_CACHE_SIZE");
+        env::remove_var("DMARC_REPORT_CACHE_MAX_AGE_SECS");
+        env::remove_var("DMARC_REPORT_CACHE_DIR");
+        env::remove_var("DMARC_WEBHOOK_AUTH_MODE");
+        env::remove_var("DMARC_WEBHOOK_AUTH_TOKEN");
+        env::remove_var("DMARC_WEBHOOK_AUTH_HEADER_NAME");
+        env::remove_var("DMARC_WEBHOOK_AUTH_HEADER_VALUE");
+        env::remove_var("DMARC_WEBHOOK_HMAC_SIGNATURE_HEADER");
+        env::remove_var("DMARC_WEBHOOK_COMPRESSION");
+        env::remove_var("DMARC_WEBHOOK_COMPRESSION_THRESHOLD_BYTES");
 
         let config = Config::new().unwrap();
         // webhook_url should be None when not set.
@@ -103,6 +353,31 @@ mod tests {
         assert_eq!(config.max_files_in_zip, 1000);
         assert_eq!(config.max_compression_ratio, 1000.0);
         assert_eq!(config.max_filename_length, 256);
+        assert!(config.mmdb_path.is_none());
+        assert!(config.mmdb_asn_path.is_none());
+        assert_eq!(config.geo_cache_size, 10_000);
+        assert_eq!(config.geo_cache_ttl_secs, 3600);
+        assert_eq!(config.geo_rate_per_min, 45);
+        assert!(config.imap_host.is_none());
+        assert_eq!(config.imap_port, 993);
+        assert!(config.imap_username.is_none());
+        assert!(config.imap_password.is_none());
+        assert_eq!(config.imap_folder, "INBOX");
+        assert!(config.alert_webhook_urls.is_empty());
+        assert_eq!(config.alert_full_failure_threshold, 10);
+        assert_eq!(config.alert_min_pass_rate_percent, 80.0);
+        assert_eq!(config.alert_timeout_secs, 30);
+        assert_eq!(config.alert_max_retries, 3);
+        assert_eq!(config.report_cache_size, 1_000);
+        assert_eq!(config.report_cache_max_age_secs, 86_400);
+        assert!(config.report_cache_dir.is_none());
+        assert_eq!(config.webhook_auth_mode, "none");
+        assert!(config.webhook_auth_token.is_none());
+        assert!(config.webhook_auth_header_name.is_none());
+        assert!(config.webhook_auth_header_value.is_none());
+        assert_eq!(config.webhook_hmac_signature_header, "X-DMARCer-Signature");
+        assert_eq!(config.webhook_compression, "disabled");
+        assert_eq!(config.webhook_compression_threshold_bytes, 1024);
     }
 
     #[test]
@@ -115,6 +390,31 @@ mod tests {
         env::set_var("DMARC_MAX_FILES_IN_ZIP", "500");
         env::set_var("DMARC_MAX_COMPRESSION_RATIO", "500.0");
         env::set_var("DMARC_MAX_FILENAME_LENGTH", "128");
+        env::set_var("DMARC_MMDB_PATH", "/etc/dmarcer/GeoLite2-City.mmdb");
+        env::set_var("DMARC_MMDB_ASN_PATH", "/etc/dmarcer/GeoLite2-ASN.mmdb");
+        env::set_var("DMARC_GEO_CACHE_SIZE", "500");
+        env::set_var("DMARC_GEO_CACHE_TTL_SECS", "120");
+        env::set_var("DMARC_GEO_RATE_PER_MIN", "90");
+        env::set_var("DMARC_IMAP_HOST", "imap.example.com");
+        env::set_var("DMARC_IMAP_PORT", "143");
+        env::set_var("DMARC_IMAP_USERNAME", "rua@example.com");
+        env::set_var("DMARC_IMAP_PASSWORD", "hunter2");
+        env::set_var("DMARC_IMAP_FOLDER", "DMARC");
+        env::set_var("DMARC_ALERT_WEBHOOK_URLS", "http://alert1.example.com, http://alert2.example.com");
+        env::set_var("DMARC_ALERT_FULL_FAILURE_THRESHOLD", "25");
+        env::set_var("DMARC_ALERT_MIN_PASS_RATE_PERCENT", "95.0");
+        env::set_var("DMARC_ALERT_TIMEOUT_SECS", "15");
+        env::set_var("DMARC_ALERT_MAX_RETRIES", "5");
+        env::set_var("DMARC_REPORT_CACHE_SIZE", "250");
+        env::set_var("DMARC_REPORT_CACHE_MAX_AGE_SECS", "600");
+        env::set_var("DMARC_REPORT_CACHE_DIR", "/tmp/dmarcer-report-cache");
+        env::set_var("DMARC_WEBHOOK_AUTH_MODE", "hmac");
+        env::set_var("DMARC_WEBHOOK_AUTH_TOKEN", "shared-secret");
+        env::set_var("DMARC_WEBHOOK_AUTH_HEADER_NAME", "X-Api-Key");
+        env::set_var("DMARC_WEBHOOK_AUTH_HEADER_VALUE", "api-key-value");
+        env::set_var("DMARC_WEBHOOK_HMAC_SIGNATURE_HEADER", "X-Signature");
+        env::set_var("DMARC_WEBHOOK_COMPRESSION", "gzip");
+        env::set_var("DMARC_WEBHOOK_COMPRESSION_THRESHOLD_BYTES", "2048");
 
         let config = Config::new().unwrap();
         assert_eq!(config.webhook_url, Some("http://example.com".to_string()));
@@ -124,5 +424,33 @@ mod tests {
         assert_eq!(config.max_files_in_zip, 500);
         assert_eq!(config.max_compression_ratio, 500.0);
         assert_eq!(config.max_filename_length, 128);
+        assert_eq!(config.mmdb_path, Some("/etc/dmarcer/GeoLite2-City.mmdb".to_string()));
+        assert_eq!(config.mmdb_asn_path, Some("/etc/dmarcer/GeoLite2-ASN.mmdb".to_string()));
+        assert_eq!(config.geo_cache_size, 500);
+        assert_eq!(config.geo_cache_ttl_secs, 120);
+        assert_eq!(config.geo_rate_per_min, 90);
+        assert_eq!(config.imap_host, Some("imap.example.com".to_string()));
+        assert_eq!(config.imap_port, 143);
+        assert_eq!(config.imap_username, Some("rua@example.com".to_string()));
+        assert_eq!(config.imap_password, Some("hunter2".to_string()));
+        assert_eq!(config.imap_folder, "DMARC");
+        assert_eq!(
+            config.alert_webhook_urls,
+            vec!["http://alert1.example.com".to_string(), "http://alert2.example.com".to_string()]
+        );
+        assert_eq!(config.alert_full_failure_threshold, 25);
+        assert_eq!(config.alert_min_pass_rate_percent, 95.0);
+        assert_eq!(config.alert_timeout_secs, 15);
+        assert_eq!(config.alert_max_retries, 5);
+        assert_eq!(config.report_cache_size, 250);
+        assert_eq!(config.report_cache_max_age_secs, 600);
+        assert_eq!(config.report_cache_dir, Some("/tmp/dmarcer-report-cache".to_string()));
+        assert_eq!(config.webhook_auth_mode, "hmac");
+        assert_eq!(config.webhook_auth_token, Some("shared-secret".to_string()));
+        assert_eq!(config.webhook_auth_header_name, Some("X-Api-Key".to_string()));
+        assert_eq!(config.webhook_auth_header_value, Some("api-key-value".to_string()));
+        assert_eq!(config.webhook_hmac_signature_header, "X-Signature");
+        assert_eq!(config.webhook_compression, "gzip");
+        assert_eq!(config.webhook_compression_threshold_bytes, 2048);
     }
 }
@@ -1,12 +1,72 @@
 //! File Handlers Module
 //!
 //! This module provides utilities for processing individual files of different types
-//! (ZIP, GZIP, XML) with appropriate security checks such as file size limits,
-//! decompression limits, and prevention of path traversal.
-use std::io::{BufReader, Read}; // Import Read trait for reading to string.
+//! (ZIP, GZIP, BZIP2, XZ, Zstandard, 7z, XML, and TAR inside GZIP; a bare uncompressed
+//! TAR is not recognized) with appropriate security checks
+//! such as file size limits, decompression limits, and prevention of path traversal.
+//!
+//! Format detection sniffs the leading magic bytes of the file rather than trusting
+//! its extension, since real-world DMARC attachments sometimes arrive with a
+//! misleading or missing extension; the extension is only used as a fallback when
+//! sniffing is inconclusive (e.g. a plain `.xml` or `.eml` file has no magic bytes).
+use std::io::{BufReader, Cursor, Read}; // Import Read trait for reading to string.
 #[allow(dead_code)]
 const BUFFER_SIZE: usize = 8192; // 8KB buffer
-/// FileHandler processes a file based on its type (ZIP, GZIP, XML).
+
+/// Number of leading bytes read while sniffing a file's format. [`detect_format`]
+/// only matches magic bytes at the start of this buffer; a tar's `ustar` magic
+/// (offset 257) is checked separately, on decompressed output in `handle_gzip`.
+///
+/// `pub(crate)` so `zip_handler::extract_zip` can share the same sniffing logic
+/// against the real ingestion paths (CLI, worker, HTTP API) rather than only
+/// this module's own tests.
+pub(crate) const SNIFF_LEN: usize = 512;
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const GZIP_MAGIC: &[u8] = b"\x1f\x8b";
+const BZIP2_MAGIC: &[u8] = b"BZh";
+const XZ_MAGIC: &[u8] = b"\xfd7zXZ\x00";
+const ZSTD_MAGIC: &[u8] = b"\x28\xb5\x2f\xfd";
+const SEVENZ_MAGIC: &[u8] = b"7z\xbc\xaf\x27\x1c";
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_MAGIC: &[u8] = b"ustar";
+
+/// A compression/archive format identified from a file's leading bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DetectedFormat {
+    Zip,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+    SevenZ,
+    /// Sniffing didn't recognize any known magic bytes; fall back to the extension.
+    Unknown,
+}
+
+/// Classifies `header` (a file's leading bytes, see [`SNIFF_LEN`]) by magic
+/// number. Shared between [`FileHandler::sniff_format`] and
+/// `zip_handler::extract_zip` so both pipelines agree on what a `.bz2`/`.xz`/
+/// `.zst`/`.7z` file actually is, regardless of its extension.
+pub(crate) fn detect_format(header: &[u8]) -> DetectedFormat {
+    if header.starts_with(ZIP_MAGIC) {
+        DetectedFormat::Zip
+    } else if header.starts_with(GZIP_MAGIC) {
+        DetectedFormat::Gzip
+    } else if header.starts_with(BZIP2_MAGIC) {
+        DetectedFormat::Bzip2
+    } else if header.starts_with(XZ_MAGIC) {
+        DetectedFormat::Xz
+    } else if header.starts_with(ZSTD_MAGIC) {
+        DetectedFormat::Zstd
+    } else if header.starts_with(SEVENZ_MAGIC) {
+        DetectedFormat::SevenZ
+    } else {
+        DetectedFormat::Unknown
+    }
+}
+
+/// FileHandler processes a file based on its type (ZIP, GZIP, BZIP2, XZ, Zstandard, 7z, XML).
 #[allow(dead_code)]
 pub struct FileHandler {
     config: crate::config::Config,
@@ -17,6 +77,13 @@ impl FileHandler {
     pub fn new(config: crate::config::Config) -> Self {
         Self { config }
     }
+    /// Reads the file's leading bytes and identifies its format by magic number.
+    fn sniff_format(&self, path: &std::path::Path) -> crate::error::Result<DetectedFormat> {
+        let mut file = std::fs::File::open(path)?;
+        let mut header = [0u8; SNIFF_LEN];
+        let read = file.read(&mut header)?;
+        Ok(detect_format(&header[..read]))
+    }
     /// Processes the file at the given path, applying security checks.
     pub fn process_file(&self, path: &std::path::Path) -> crate::error::Result<Vec<String>> {
         if !path.exists() {
@@ -33,6 +100,15 @@ impl FileHandler {
                 self.config.max_file_size
             )));
         }
+        match self.sniff_format(path)? {
+            DetectedFormat::Zip => return self.handle_zip(path),
+            DetectedFormat::Gzip => return self.handle_gzip(path),
+            DetectedFormat::Bzip2 => return self.handle_bzip2(path),
+            DetectedFormat::Xz => return self.handle_xz(path),
+            DetectedFormat::Zstd => return self.handle_zstd(path),
+            DetectedFormat::SevenZ => return self.handle_sevenz(path),
+            DetectedFormat::Unknown => {}
+        }
         match path.extension()
             .and_then(|ext| ext.to_str())
             .map(|s| s.to_lowercase())
@@ -40,12 +116,115 @@ impl FileHandler {
             Some(ext) => match ext.as_str() {
                 "zip" => self.handle_zip(path),
                 "gz" => self.handle_gzip(path),
+                "bz2" => self.handle_bzip2(path),
+                "xz" | "lzma" => self.handle_xz(path),
+                "zst" => self.handle_zstd(path),
+                "7z" => self.handle_sevenz(path),
                 "xml" => self.handle_xml(path),
+                "eml" | "msg" => self.handle_eml(path),
+                "json" => self.handle_json(path),
                 _ => Err(crate::error::DmarcError::UnsupportedFile(format!("Unsupported file extension: {}", ext))),
             },
             None => Err(crate::error::DmarcError::UnsupportedFile("No file extension".into())),
         }
     }
+    /// Reads `decoder` to completion under decompression-bomb guards: rejects output
+    /// past `max_decompressed_size`, and past `max_compression_ratio` relative to the
+    /// original (compressed) file size.
+    fn decompress_capped<R: Read>(&self, mut decoder: R, compressed_size: u64, label: &str) -> crate::error::Result<Vec<u8>> {
+        let cap = self.config.max_decompressed_size;
+        let mut buf = Vec::new();
+        let mut limited = (&mut decoder).take(cap as u64 + 1);
+        limited.read_to_end(&mut buf)?;
+        if buf.len() > cap {
+            return Err(crate::error::DmarcError::FileTooLarge(format!("Decompressed {} size exceeds limit", label)));
+        }
+        if compressed_size > 0 {
+            let ratio = buf.len() as f64 / compressed_size as f64;
+            if ratio > self.config.max_compression_ratio {
+                return Err(crate::error::DmarcError::Format(format!("Suspicious {} compression ratio: {:.2}", label, ratio)));
+            }
+        }
+        Ok(buf)
+    }
+    /// Handles extraction from BZIP2 archives.
+    fn handle_bzip2(&self, path: &std::path::Path) -> crate::error::Result<Vec<String>> {
+        let file = std::fs::File::open(path)?;
+        let compressed_size = file.metadata()?.len();
+        let decoder = bzip2::read::BzDecoder::new(BufReader::with_capacity(BUFFER_SIZE, file));
+        let buf = self.decompress_capped(decoder, compressed_size, "BZ2")?;
+        let contents = String::from_utf8(buf).map_err(|_| crate::error::DmarcError::Parse("Invalid UTF-8 in decompressed BZ2 file".into()))?;
+        if contents.trim().is_empty() {
+            return Err(crate::error::DmarcError::Parse("Empty BZ2 file".into()));
+        }
+        Ok(vec![contents])
+    }
+    /// Handles extraction from XZ/LZMA archives.
+    fn handle_xz(&self, path: &std::path::Path) -> crate::error::Result<Vec<String>> {
+        let file = std::fs::File::open(path)?;
+        let compressed_size = file.metadata()?.len();
+        let decoder = xz2::read::XzDecoder::new(BufReader::with_capacity(BUFFER_SIZE, file));
+        let buf = self.decompress_capped(decoder, compressed_size, "XZ")?;
+        let contents = String::from_utf8(buf).map_err(|_| crate::error::DmarcError::Parse("Invalid UTF-8 in decompressed XZ file".into()))?;
+        if contents.trim().is_empty() {
+            return Err(crate::error::DmarcError::Parse("Empty XZ file".into()));
+        }
+        Ok(vec![contents])
+    }
+    /// Handles extraction from Zstandard-compressed files.
+    fn handle_zstd(&self, path: &std::path::Path) -> crate::error::Result<Vec<String>> {
+        let file = std::fs::File::open(path)?;
+        let compressed_size = file.metadata()?.len();
+        let decoder = zstd::stream::read::Decoder::new(BufReader::with_capacity(BUFFER_SIZE, file))
+            .map_err(crate::error::DmarcError::Io)?;
+        let buf = self.decompress_capped(decoder, compressed_size, "Zstandard")?;
+        let contents = String::from_utf8(buf).map_err(|_| crate::error::DmarcError::Parse("Invalid UTF-8 in decompressed Zstandard file".into()))?;
+        if contents.trim().is_empty() {
+            return Err(crate::error::DmarcError::Parse("Empty Zstandard file".into()));
+        }
+        Ok(vec![contents])
+    }
+    /// Handles extraction from `.7z` archives. 7-Zip's format is block-oriented rather
+    /// than a single decompression stream, so entries are read out individually.
+    ///
+    /// An entry's header-declared `size()` is attacker-controlled and must not be
+    /// trusted (the same reasoning as chunk2-1's zip rewrite), so each entry is run
+    /// through `decompress_capped`, which counts actual bytes read against
+    /// `max_decompressed_size`/`max_compression_ratio` rather than the declared size.
+    fn handle_sevenz(&self, path: &std::path::Path) -> crate::error::Result<Vec<String>> {
+        let compressed_size = std::fs::metadata(path)?.len();
+        let mut reader = sevenz_rust::SevenZReader::open(path, sevenz_rust::Password::empty())
+            .map_err(|e| crate::error::DmarcError::Format(format!("Invalid 7z archive: {}", e)))?;
+        let mut contents_vec = Vec::new();
+        reader.for_each_entries(|entry, entry_reader| {
+            let buf = self
+                .decompress_capped(entry_reader, compressed_size, "7z")
+                .map_err(|e| sevenz_rust::Error::other(format!("File in 7z rejected ({}): {}", entry.name(), e)))?;
+            let contents = String::from_utf8(buf)
+                .map_err(|_| sevenz_rust::Error::other(format!("Invalid UTF-8 in 7z entry: {}", entry.name())))?;
+            if !contents.trim().is_empty() {
+                contents_vec.push(contents);
+            }
+            Ok(true)
+        }).map_err(|e| crate::error::DmarcError::Format(format!("Failed to read 7z archive: {}", e)))?;
+        if contents_vec.is_empty() {
+            return Err(crate::error::DmarcError::Parse("No valid files found in 7z archive".into()));
+        }
+        Ok(contents_vec)
+    }
+    /// Extracts a `.7z` archive with the same decompressed-size/compression-ratio
+    /// guards `handle_sevenz` applies. `sevenz_rust` has no async reader, so
+    /// `zip_handler::extract_zip` runs this on a blocking thread rather than
+    /// reimplementing 7z decoding itself.
+    pub(crate) fn extract_sevenz(&self, path: &std::path::Path) -> crate::error::Result<Vec<String>> {
+        self.handle_sevenz(path)
+    }
+    /// Reads the forensic/failure report (`.eml`/`.msg`, ARF format) at `path` and
+    /// parses it with [`crate::forensic_parser::parse_forensic_report`].
+    pub fn process_forensic_file(&self, path: &std::path::Path) -> crate::error::Result<crate::models::ForensicReport> {
+        let raw = self.handle_eml(path)?.remove(0);
+        crate::forensic_parser::parse_forensic_report(&raw)
+    }
     /// Handles extraction from ZIP archives.
     fn handle_zip(&self, path: &std::path::Path) -> crate::error::Result<Vec<String>> {
         let file = std::fs::File::open(path)?;
@@ -72,23 +251,94 @@ impl FileHandler {
         }
         Ok(contents_vec)
     }
-    /// Handles extraction from GZIP archives.
+    /// Handles extraction from GZIP archives, including `.tar.gz` bundles (detected by
+    /// the `ustar` magic at offset 257 in the decompressed stream).
     fn handle_gzip(&self, path: &std::path::Path) -> crate::error::Result<Vec<String>> {
         let file = std::fs::File::open(path)?;
-        let mut gz = flate2::read::GzDecoder::new(BufReader::with_capacity(BUFFER_SIZE, file));
-        let mut contents = String::new();
-        let len = gz.read_to_string(&mut contents)?;
-        if len > self.config.max_file_size {
-            return Err(crate::error::DmarcError::FileTooLarge(format!(
-                "Decompressed GZ size {} bytes exceeds limit",
-                len
-            )));
+        let compressed_size = file.metadata()?.len();
+        let gz = flate2::read::GzDecoder::new(BufReader::with_capacity(BUFFER_SIZE, file));
+        let buf = self.decompress_capped(gz, compressed_size, "GZ")?;
+
+        if buf.len() > TAR_MAGIC_OFFSET + TAR_MAGIC.len()
+            && &buf[TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len()] == TAR_MAGIC
+        {
+            return self.handle_tar_bytes(buf);
         }
+
+        let contents = String::from_utf8(buf).map_err(|_| crate::error::DmarcError::Parse("Invalid UTF-8 in decompressed GZ file".into()))?;
         if contents.trim().is_empty() {
             return Err(crate::error::DmarcError::Parse("Empty GZ file".into()));
         }
         Ok(vec![contents])
     }
+    /// Reads each entry of an in-memory tar stream, rejecting path-traversal names,
+    /// names longer than `max_filename_length`, and entries larger than `max_file_size`.
+    fn handle_tar_bytes(&self, buf: Vec<u8>) -> crate::error::Result<Vec<String>> {
+        let max_size = self.config.max_file_size;
+        let mut archive = tar::Archive::new(Cursor::new(buf));
+        let mut contents_vec = Vec::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let inner_name = entry.path()?.to_string_lossy().to_string();
+            if inner_name.contains("..") || inner_name.starts_with('/') || inner_name.starts_with('\\') {
+                return Err(crate::error::DmarcError::Format(format!("Path traversal attempt detected: {}", inner_name)));
+            }
+            if inner_name.len() > self.config.max_filename_length {
+                return Err(crate::error::DmarcError::Format("Filename too long".to_string()));
+            }
+            if entry.size() > max_size as u64 {
+                return Err(crate::error::DmarcError::FileTooLarge(format!("File in TAR too large: {}", inner_name)));
+            }
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents)?;
+            if !contents.trim().is_empty() {
+                contents_vec.push(contents);
+            }
+        }
+        if contents_vec.is_empty() {
+            return Err(crate::error::DmarcError::Parse("No valid files found in TAR archive".into()));
+        }
+        Ok(contents_vec)
+    }
+    /// Handles reading from forensic/failure report files (`.eml`/`.msg`, ARF format).
+    /// These are plain MIME messages, not XML, so they're read as-is and handed to
+    /// the `forensic_parser` module rather than `xml_parser`.
+    fn handle_eml(&self, path: &std::path::Path) -> crate::error::Result<Vec<String>> {
+        let file = std::fs::File::open(path)?;
+        let mut reader = BufReader::new(file);
+        let mut contents = String::new();
+        let len = reader.read_to_string(&mut contents)?;
+        if len as u64 > self.config.max_file_size as u64 {
+            return Err(crate::error::DmarcError::FileTooLarge("Forensic report file size too large".to_string()));
+        }
+        if contents.trim().is_empty() {
+            return Err(crate::error::DmarcError::Parse("Empty forensic report file".into()));
+        }
+        Ok(vec![contents])
+    }
+    /// Handles reading from plain (uncompressed) TLS-RPT JSON files. Gzip-compressed
+    /// TLS-RPT reports (the common case) never reach this method: `sniff_format`
+    /// recognizes the GZIP magic bytes first and routes to `handle_gzip`, which
+    /// returns the decompressed JSON text the same way it would decompressed XML.
+    fn handle_json(&self, path: &std::path::Path) -> crate::error::Result<Vec<String>> {
+        let file = std::fs::File::open(path)?;
+        let mut reader = BufReader::new(file);
+        let mut contents = String::new();
+        let len = reader.read_to_string(&mut contents)?;
+        if len as u64 > self.config.max_file_size as u64 {
+            return Err(crate::error::DmarcError::FileTooLarge("JSON file size too large".to_string()));
+        }
+        if contents.trim().is_empty() {
+            return Err(crate::error::DmarcError::Parse("Empty JSON file".into()));
+        }
+        Ok(vec![contents])
+    }
+    /// Reads the TLS-RPT report (RFC 8460, plain or gzip-compressed JSON) at `path`
+    /// and parses it with [`crate::tlsrpt::parse_tlsrpt_json`].
+    pub fn process_tlsrpt_file(&self, path: &std::path::Path) -> crate::error::Result<crate::tlsrpt::TlsRptReport> {
+        let raw = self.process_file(path)?.remove(0);
+        crate::tlsrpt::parse_tlsrpt_json(&raw)
+    }
     /// Handles reading from plain XML files.
     fn handle_xml(&self, path: &std::path::Path) -> crate::error::Result<Vec<String>> {
         let file = std::fs::File::open(path)?;
@@ -124,15 +374,9 @@ mod tests {
         zip.start_file("test.xml", options)?;
         zip.write_all(b"<feedback></feedback>")?;
         zip.finish()?;
-        let config = crate::config::Config {
-            max_file_size: 1024 * 1024,
-            webhook_url: None,
-            webhook_timeout: 30,
-            max_decompressed_size: 1024 * 1024,
-            max_files_in_zip: 1000,
-            max_compression_ratio: 1000.0,
-            max_filename_length: 256,
-        };
+        let mut config = crate::config::Config::new().unwrap();
+        config.max_file_size = 1024 * 1024;
+        config.max_decompressed_size = 1024 * 1024;
         let handler = FileHandler::new(config);
         let result = handler.process_file(&zip_path)?;
         assert!(!result.is_empty());
@@ -146,15 +390,9 @@ mod tests {
         let mut gz = flate2::write::GzEncoder::new(file, flate2::Compression::default());
         gz.write_all(b"<feedback></feedback>")?;
         gz.finish()?;
-        let config = crate::config::Config {
-            max_file_size: 1024 * 1024,
-            webhook_url: None,
-            webhook_timeout: 30,
-            max_decompressed_size: 1024 * 1024,
-            max_files_in_zip: 1000,
-            max_compression_ratio: 1000.0,
-            max_filename_length: 256,
-        };
+        let mut config = crate::config::Config::new().unwrap();
+        config.max_file_size = 1024 * 1024;
+        config.max_decompressed_size = 1024 * 1024;
         let handler = FileHandler::new(config);
         let result = handler.process_file(&gz_path)?;
         assert!(!result.is_empty());
@@ -167,15 +405,9 @@ mod tests {
         let mut file = std::fs::File::create(&xml_path)?;
         let large_content = "A".repeat(1024 * 1024 + 1);
         file.write_all(large_content.as_bytes())?;
-        let config = crate::config::Config {
-            max_file_size: 1024 * 1024,
-            webhook_url: None,
-            webhook_timeout: 30,
-            max_decompressed_size: 1024 * 1024,
-            max_files_in_zip: 1000,
-            max_compression_ratio: 1000.0,
-            max_filename_length: 256,
-        };
+        let mut config = crate::config::Config::new().unwrap();
+        config.max_file_size = 1024 * 1024;
+        config.max_decompressed_size = 1024 * 1024;
         let handler = FileHandler::new(config);
         let result = handler.process_file(&xml_path);
         assert!(matches!(result, Err(crate::error::DmarcError::FileTooLarge(_))));
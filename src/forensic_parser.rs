@@ -0,0 +1,185 @@
+//! Forensic/Failure Report Parser (AFRF, RFC 6591)
+//!
+//! Aggregate reports (see `xml_parser`) summarize a sending domain's traffic once a
+//! day; forensic reports (the `ruf` stream) are sent per offending message, as a
+//! MIME `multipart/report` email: a human-readable preamble, a `message/feedback-report`
+//! part carrying `Feedback-Type: auth-failure` plus the failure details as
+//! `Header: value` lines, and a `message/rfc822` (or `text/rfc822-headers`) part with
+//! the headers of the message that triggered the report. This module parses that
+//! structure directly rather than pulling in a general-purpose MIME crate, since a
+//! forensic report's shape is narrow and fixed by RFC 6591/5965.
+
+use crate::error::{DmarcError, Result};
+use crate::models::ForensicReport;
+
+/// Parses a raw `multipart/report` forensic report message into a [`ForensicReport`].
+pub fn parse_forensic_report(raw: &str) -> Result<ForensicReport> {
+    let normalized = raw.replace("\r\n", "\n");
+    let (headers, body) = split_headers_and_body(&normalized)
+        .ok_or_else(|| DmarcError::Format("Forensic report has no header/body separator".into()))?;
+
+    let boundary = content_type_param(&headers, "boundary")
+        .ok_or_else(|| DmarcError::Format("Forensic report is missing a MIME boundary".into()))?;
+
+    let parts = split_mime_parts(body, &boundary);
+
+    let mut report = ForensicReport::default();
+    let mut found_feedback_part = false;
+
+    for part in &parts {
+        let Some((part_headers, part_body)) = split_headers_and_body(part) else {
+            continue;
+        };
+        let content_type = header_value(&part_headers, "Content-Type").unwrap_or_default();
+        let content_type = content_type.to_lowercase();
+
+        if content_type.starts_with("message/feedback-report") {
+            parse_feedback_report(part_body, &mut report);
+            found_feedback_part = true;
+        } else if content_type.starts_with("message/rfc822") || content_type.starts_with("text/rfc822-headers") {
+            let original_headers = split_headers_and_body(part_body)
+                .map(|(h, _)| h)
+                .unwrap_or_else(|| part_body.to_string());
+            report.original_headers = Some(original_headers.trim().to_string());
+        }
+    }
+
+    if !found_feedback_part {
+        return Err(DmarcError::Format("Forensic report has no message/feedback-report part".into()));
+    }
+    if report.feedback_type.is_empty() {
+        return Err(DmarcError::Format("Forensic report is missing Feedback-Type".into()));
+    }
+
+    Ok(report)
+}
+
+/// Splits `text` into its header block and body at the first blank line.
+fn split_headers_and_body(text: &str) -> Option<(String, &str)> {
+    let idx = text.find("\n\n")?;
+    Some((text[..idx].to_string(), &text[idx + 2..]))
+}
+
+/// Looks up a header by name (case-insensitive), unfolding continuation lines.
+fn header_value(headers: &str, name: &str) -> Option<String> {
+    let mut lines = headers.lines().peekable();
+    while let Some(line) = lines.next() {
+        let Some((key, value)) = line.split_once(':') else { continue };
+        if !key.trim().eq_ignore_ascii_case(name) {
+            continue;
+        }
+        let mut value = value.trim().to_string();
+        while let Some(next) = lines.peek() {
+            if next.starts_with(' ') || next.starts_with('\t') {
+                value.push(' ');
+                value.push_str(next.trim());
+                lines.next();
+            } else {
+                break;
+            }
+        }
+        return Some(value);
+    }
+    None
+}
+
+/// Extracts the `boundary` parameter from a header block's `Content-Type`, for
+/// callers outside this module that need to split their own `multipart/*` body
+/// (e.g. `mailbox`, splitting a raw email into its MIME parts).
+pub(crate) fn content_type_boundary(headers: &str) -> Option<String> {
+    content_type_param(headers, "boundary")
+}
+
+/// Extracts a `; key=value` (optionally quoted) parameter from the `Content-Type` header.
+fn content_type_param(headers: &str, param: &str) -> Option<String> {
+    let content_type = header_value(headers, "Content-Type")?;
+    for segment in content_type.split(';').skip(1) {
+        let segment = segment.trim();
+        let (key, value) = segment.split_once('=')?;
+        if key.trim().eq_ignore_ascii_case(param) {
+            return Some(value.trim().trim_matches('"').to_string());
+        }
+    }
+    None
+}
+
+/// Splits a `multipart/report` body on `--boundary` markers, dropping the preamble
+/// and epilogue.
+fn split_mime_parts<'a>(body: &'a str, boundary: &str) -> Vec<&'a str> {
+    let delimiter = format!("--{}", boundary);
+    body.split(delimiter.as_str())
+        .skip(1)
+        .map(|part| part.trim_start_matches('\n'))
+        .filter(|part| !part.trim_start().starts_with("--"))
+        .collect()
+}
+
+/// Parses the `Header: value` lines of a `message/feedback-report` part.
+fn parse_feedback_report(part_body: &str, report: &mut ForensicReport) {
+    for line in part_body.lines() {
+        let Some((key, value)) = line.split_once(':') else { continue };
+        let value = value.trim().to_string();
+        match key.trim().to_lowercase().as_str() {
+            "feedback-type" => report.feedback_type = value,
+            "user-agent" => report.user_agent = Some(value),
+            "version" => report.version = Some(value),
+            "original-mail-from" => report.original_mail_from = Some(value),
+            "source-ip" => report.source_ip = Some(value),
+            "reported-domain" => report.reported_domain = Some(value),
+            "delivery-result" => report.delivery_result = Some(value),
+            "auth-failure" => report.auth_failure.push(value),
+            "authentication-results" => report.authentication_results = Some(value),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "From: mailer-daemon@example.com\n\
+Content-Type: multipart/report; report-type=feedback-report;\n\
+\tboundary=\"boundary42\"\n\
+\n\
+--boundary42\n\
+Content-Type: text/plain\n\
+\n\
+This is a DMARC forensic report.\n\
+--boundary42\n\
+Content-Type: message/feedback-report\n\
+\n\
+Feedback-Type: auth-failure\n\
+User-Agent: DMARCer/1.0\n\
+Version: 1\n\
+Original-Mail-From: <sender@example.com>\n\
+Source-IP: 203.0.113.9\n\
+Reported-Domain: example.com\n\
+Delivery-Result: delivered\n\
+Auth-Failure: dkim\n\
+Auth-Failure: spf\n\
+Authentication-Results: mx.example.org; dkim=fail; spf=fail\n\
+--boundary42\n\
+Content-Type: message/rfc822\n\
+\n\
+From: sender@example.com\n\
+To: recipient@example.org\n\
+Subject: Test message\n\
+--boundary42--\n";
+
+    #[test]
+    fn test_parse_forensic_report() {
+        let report = parse_forensic_report(SAMPLE).unwrap();
+        assert_eq!(report.feedback_type, "auth-failure");
+        assert_eq!(report.source_ip.as_deref(), Some("203.0.113.9"));
+        assert_eq!(report.reported_domain.as_deref(), Some("example.com"));
+        assert_eq!(report.auth_failure, vec!["dkim".to_string(), "spf".to_string()]);
+        assert!(report.original_headers.as_deref().unwrap().contains("Subject: Test message"));
+    }
+
+    #[test]
+    fn test_parse_forensic_report_missing_boundary() {
+        let result = parse_forensic_report("Content-Type: multipart/report\n\nno boundary here");
+        assert!(result.is_err());
+    }
+}
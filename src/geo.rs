@@ -1,17 +1,126 @@
 //! Geolocation Module
 //!
-//! This module provides IP geolocation using the IP-API service. Results are cached
-//! to reduce redundant lookups and improve performance. It also provides utilities
-//! to clear and check the cache.
+//! This module provides IP geolocation, preferring an offline MaxMind GeoLite2/GeoIP2
+//! `.mmdb` database (via `DMARC_MMDB_PATH`/`DMARC_MMDB_ASN_PATH`) when configured, and
+//! falling back to the online IP-API service otherwise. Results are cached in a bounded
+//! LRU (capped at `DMARC_GEO_CACHE_SIZE` entries) so a long-running worker doesn't grow
+//! memory without limit, and each entry expires after `DMARC_GEO_CACHE_TTL_SECS` so
+//! ASN/geo data can refresh as networks re-assign IPs.
+//!
+//! Outbound IP-API calls are paced through a token-bucket limiter (`DMARC_GEO_RATE_PER_MIN`)
+//! to stay under the free tier's quota, and [`GeoLookup::lookup_ips`] resolves many IPs at
+//! once via IP-API's batch endpoint (in chunks of up to 100) to cut round-trips when a
+//! report carries hundreds of unique source IPs.
+use crate::config::Config;
 use crate::error::{DmarcError, Result};
 use crate::models::IpGeoInfo;
 use ipgeolocate::{Locator, Service};
+use lru::LruCache;
+use maxminddb::geoip2;
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::net::IpAddr;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
 use lazy_static::lazy_static;
 use tracing::{info, warn};
+
+const DEFAULT_CACHE_SIZE: usize = 10_000;
+const IP_API_BATCH_URL: &str = "http://ip-api.com/batch";
+const IP_API_BATCH_CHUNK_SIZE: usize = 100;
+
 lazy_static! {
-    static ref IP_CACHE: Mutex<HashMap<String, IpGeoInfo>> = Mutex::new(HashMap::new());
+    static ref IP_CACHE: Mutex<LruCache<String, (IpGeoInfo, Instant)>> =
+        Mutex::new(LruCache::new(NonZeroUsize::new(DEFAULT_CACHE_SIZE).unwrap()));
+    static ref MMDB_CITY_READER: RwLock<Option<maxminddb::Reader<Vec<u8>>>> = RwLock::new(None);
+    static ref MMDB_ASN_READER: RwLock<Option<maxminddb::Reader<Vec<u8>>>> = RwLock::new(None);
+    static ref RATE_LIMITER: Mutex<TokenBucket> = Mutex::new(TokenBucket::new(45));
+}
+
+/// A simple token-bucket limiter used to pace outbound IP-API requests.
+struct TokenBucket {
+    tokens: f64,
+    capacity: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate_per_min: u32) -> Self {
+        let capacity = rate_per_min.max(1) as f64;
+        TokenBucket {
+            tokens: capacity,
+            capacity,
+            refill_per_sec: capacity / 60.0,
+            last_refill: Instant::now(),
+        }
+    }
+    /// Adjusts the bucket's rate when the configured limit changes, without resetting
+    /// the tokens currently available.
+    fn reconfigure(&mut self, rate_per_min: u32) {
+        let capacity = rate_per_min.max(1) as f64;
+        if (self.capacity - capacity).abs() > f64::EPSILON {
+            self.capacity = capacity;
+            self.refill_per_sec = capacity / 60.0;
+            self.tokens = self.tokens.min(capacity);
+        }
+    }
+    fn refill(&mut self) {
+        let elapsed = self.last_refill.elapsed().as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = Instant::now();
+    }
+    /// Consumes a token if one is available, otherwise returns how long to wait for one.
+    fn try_acquire(&mut self) -> Option<Duration> {
+        self.refill();
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            let deficit = 1.0 - self.tokens;
+            Some(Duration::from_secs_f64(deficit / self.refill_per_sec))
+        }
+    }
+}
+
+/// Blocks until the token bucket (sized to `rate_per_min`) has a token available.
+async fn throttle(rate_per_min: u32) {
+    loop {
+        let wait = {
+            let mut bucket = RATE_LIMITER.lock().unwrap();
+            bucket.reconfigure(rate_per_min);
+            bucket.try_acquire()
+        };
+        match wait {
+            None => return,
+            Some(delay) => tokio::time::sleep(delay).await,
+        }
+    }
+}
+
+/// Resizes the LRU cache to `size` entries if it doesn't already match, discarding the
+/// least-recently-used entries when shrinking.
+fn resize_cache(size: usize) {
+    let mut cache = IP_CACHE.lock().unwrap();
+    if let Some(new_cap) = NonZeroUsize::new(size) {
+        if cache.cap() != new_cap {
+            cache.resize(new_cap);
+        }
+    }
+}
+
+/// Returns the cached entry for `ip` if present and not older than `ttl`, evicting it
+/// (treating it as a miss) when it has expired.
+fn get_fresh(ip: &str, ttl: Duration) -> Option<IpGeoInfo> {
+    let mut cache = IP_CACHE.lock().unwrap();
+    let is_fresh = cache.peek(ip).map(|(_, inserted)| inserted.elapsed() < ttl).unwrap_or(false);
+    if is_fresh {
+        cache.get(ip).map(|(info, _)| info.clone())
+    } else {
+        cache.pop(ip);
+        None
+    }
 }
 /// GeoLookup provides asynchronous IP geolocation with caching.
 #[allow(dead_code)]
@@ -19,11 +128,27 @@ pub struct GeoLookup;
 #[allow(dead_code)]
 impl GeoLookup {
     /// Looks up the geolocation for the given IP address.
-    /// First checks the cache; if not found, performs a lookup via IP-API.
-    pub async fn lookup_ip(ip: &str) -> Result<IpGeoInfo> {
-        if let Some(cached_info) = IP_CACHE.lock().unwrap().get(ip) {
-            return Ok(cached_info.clone());
+    ///
+    /// Checks the cache first, treating entries older than `config.geo_cache_ttl_secs`
+    /// as misses. On a miss, if `config.mmdb_path` is set, opens (and caches) the
+    /// MaxMind reader and resolves the IP offline; an `asn` database additionally
+    /// fills `asn`/`organization`. Falls back to the online IP-API service when no
+    /// mmdb is configured or the IP has no mmdb record.
+    pub async fn lookup_ip(ip: &str, config: &Config) -> Result<IpGeoInfo> {
+        resize_cache(config.geo_cache_size);
+        let ttl = Duration::from_secs(config.geo_cache_ttl_secs);
+        if let Some(cached_info) = get_fresh(ip, ttl) {
+            return Ok(cached_info);
+        }
+        if let Some(mmdb_path) = &config.mmdb_path {
+            if let Some(geo_info) = Self::lookup_ip_offline(ip, mmdb_path, config.mmdb_asn_path.as_deref())? {
+                IP_CACHE.lock().unwrap().put(ip.to_string(), (geo_info.clone(), Instant::now()));
+                info!("Successful offline geolocation lookup for IP: {}", ip);
+                return Ok(geo_info);
+            }
+            warn!("No offline geolocation record for IP {}, falling back to IP-API", ip);
         }
+        throttle(config.geo_rate_per_min).await;
         match Locator::get(ip, Service::IpApi).await {
             Ok(location) => {
                 let geo_info = IpGeoInfo {
@@ -34,7 +159,7 @@ impl GeoLookup {
                     asn: None, // IP-API free tier doesn't provide ASN
                     organization: None,
                 };
-                IP_CACHE.lock().unwrap().insert(ip.to_string(), geo_info.clone());
+                IP_CACHE.lock().unwrap().put(ip.to_string(), (geo_info.clone(), Instant::now()));
                 info!("Successful geolocation lookup for IP: {}", ip);
                 Ok(geo_info)
             }
@@ -44,6 +169,168 @@ impl GeoLookup {
             }
         }
     }
+    /// Resolves many IPs at once, preferring cache hits. When `config.mmdb_path` is set,
+    /// each cache miss goes through [`GeoLookup::lookup_ip`] on its own (so an IP with no
+    /// mmdb record falls back to a single IP-API request); otherwise the cache misses are
+    /// collapsed into chunks of up to 100 resolved through IP-API's batch endpoint in a
+    /// single POST per chunk. Every returned entry is cached.
+    pub async fn lookup_ips(ips: &[String], config: &Config) -> Result<HashMap<String, IpGeoInfo>> {
+        resize_cache(config.geo_cache_size);
+        let ttl = Duration::from_secs(config.geo_cache_ttl_secs);
+
+        let mut results = HashMap::new();
+        let mut online_misses = Vec::new();
+        for ip in ips {
+            if let Some(info) = get_fresh(ip, ttl) {
+                results.insert(ip.clone(), info);
+                continue;
+            }
+            if config.mmdb_path.is_some() {
+                match Self::lookup_ip(ip, config).await {
+                    Ok(info) => {
+                        results.insert(ip.clone(), info);
+                    }
+                    Err(e) => warn!("Geolocation lookup failed for IP {}: {}", ip, e),
+                }
+                continue;
+            }
+            online_misses.push(ip.clone());
+        }
+
+        for chunk in online_misses.chunks(IP_API_BATCH_CHUNK_SIZE) {
+            throttle(config.geo_rate_per_min).await;
+            match Self::lookup_ip_api_batch(chunk).await {
+                Ok(batch_results) => {
+                    for (ip, info) in batch_results {
+                        IP_CACHE.lock().unwrap().put(ip.clone(), (info.clone(), Instant::now()));
+                        results.insert(ip, info);
+                    }
+                }
+                Err(e) => warn!("Batch geolocation lookup failed: {}", e),
+            }
+        }
+        Ok(results)
+    }
+    /// Resolves up to 100 IPs in a single POST to IP-API's batch endpoint.
+    async fn lookup_ip_api_batch(ips: &[String]) -> Result<Vec<(String, IpGeoInfo)>> {
+        #[derive(serde::Deserialize)]
+        struct BatchEntry {
+            query: String,
+            status: String,
+            country: Option<String>,
+            city: Option<String>,
+            lat: Option<f64>,
+            lon: Option<f64>,
+            #[serde(rename = "as")]
+            asn: Option<String>,
+            #[serde(rename = "isp")]
+            organization: Option<String>,
+        }
+
+        let body: Vec<serde_json::Value> = ips.iter().map(|ip| serde_json::json!({ "query": ip })).collect();
+        let client = reqwest::Client::new();
+        let response = client
+            .post(IP_API_BATCH_URL)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| DmarcError::Geolocation(e.to_string()))?;
+        let entries: Vec<BatchEntry> = response
+            .json()
+            .await
+            .map_err(|e| DmarcError::Geolocation(e.to_string()))?;
+
+        Ok(entries
+            .into_iter()
+            .filter(|entry| entry.status == "success")
+            .map(|entry| {
+                let info = IpGeoInfo {
+                    country: entry.country.unwrap_or_default(),
+                    city: entry.city,
+                    latitude: entry.lat.unwrap_or(0.0),
+                    longitude: entry.lon.unwrap_or(0.0),
+                    asn: entry.asn,
+                    organization: entry.organization,
+                };
+                (entry.query, info)
+            })
+            .collect())
+    }
+    /// Performs an offline City (and optional ASN) lookup against the configured mmdb files.
+    /// Returns `Ok(None)` when the IP has no City record, which signals the caller to fall
+    /// back to the online service.
+    fn lookup_ip_offline(ip: &str, mmdb_path: &str, asn_path: Option<&str>) -> Result<Option<IpGeoInfo>> {
+        let addr = IpAddr::from_str(ip).map_err(|e| DmarcError::Geolocation(format!("Invalid IP address {}: {}", ip, e)))?;
+
+        Self::open_reader(&MMDB_CITY_READER, mmdb_path)?;
+        let city_guard = MMDB_CITY_READER.read().unwrap();
+        let city_reader = city_guard.as_ref().expect("City reader initialized by open_reader");
+
+        let city: geoip2::City = match city_reader.lookup(addr) {
+            Ok(record) => record,
+            Err(maxminddb::MaxMindDBError::AddressNotFoundError(_)) => return Ok(None),
+            Err(e) => return Err(DmarcError::Geolocation(e.to_string())),
+        };
+
+        let country = city
+            .country
+            .as_ref()
+            .and_then(|c| c.names.as_ref())
+            .and_then(|names| names.get("en"))
+            .map(|s| s.to_string())
+            .unwrap_or_default();
+        let city_name = city
+            .city
+            .as_ref()
+            .and_then(|c| c.names.as_ref())
+            .and_then(|names| names.get("en"))
+            .map(|s| s.to_string());
+        let (latitude, longitude) = city
+            .location
+            .as_ref()
+            .map(|loc| (loc.latitude.unwrap_or(0.0), loc.longitude.unwrap_or(0.0)))
+            .unwrap_or((0.0, 0.0));
+
+        let mut asn = None;
+        let mut organization = None;
+        if let Some(asn_path) = asn_path {
+            Self::open_reader(&MMDB_ASN_READER, asn_path)?;
+            let asn_guard = MMDB_ASN_READER.read().unwrap();
+            let asn_reader = asn_guard.as_ref().expect("ASN reader initialized by open_reader");
+            if let Ok(record) = asn_reader.lookup::<geoip2::Asn>(addr) {
+                asn = record.autonomous_system_number.map(|n| format!("AS{}", n));
+                organization = record.autonomous_system_organization.map(|s| s.to_string());
+            }
+        }
+
+        Ok(Some(IpGeoInfo {
+            country,
+            city: city_name,
+            latitude,
+            longitude,
+            asn,
+            organization,
+        }))
+    }
+    /// Lazily opens an mmdb reader into `slot`, reusing it on subsequent calls.
+    ///
+    /// The reader is never reopened once set, even if a later `Config` (e.g. after
+    /// `Config::reload`'s hot-reload) points `mmdb_path`/`mmdb_asn_path` at a
+    /// different file — whichever path won the first successful open for this
+    /// process is the one served for its lifetime.
+    fn open_reader(slot: &RwLock<Option<maxminddb::Reader<Vec<u8>>>>, path: &str) -> Result<()> {
+        if slot.read().unwrap().is_some() {
+            return Ok(());
+        }
+        let mut slot = slot.write().unwrap();
+        if slot.is_some() {
+            return Ok(());
+        }
+        let reader = maxminddb::Reader::open_readfile(path)
+            .map_err(|e| DmarcError::Geolocation(format!("Failed to open mmdb file {}: {}", path, e)))?;
+        *slot = Some(reader);
+        Ok(())
+    }
     /// Clears the IP geolocation cache.
     pub fn clear_cache() {
         IP_CACHE.lock().unwrap().clear();
@@ -58,9 +345,21 @@ impl GeoLookup {
 mod tests {
     use super::*;
     use tokio;
+
+    lazy_static! {
+        /// `IP_CACHE` (and its capacity, via `resize_cache`) is process-global state,
+        /// so tests that mutate it must not run concurrently with each other — the
+        /// test runner otherwise interleaves them on separate threads and one test's
+        /// `resize_cache`/`clear_cache` stomps on another's assertions. Each such test
+        /// takes this lock for its duration instead of relying on `--test-threads=1`.
+        static ref CACHE_TEST_LOCK: Mutex<()> = Mutex::new(());
+    }
+
     #[tokio::test]
     async fn test_ip_lookup() {
-        let result = GeoLookup::lookup_ip("8.8.8.8").await;
+        let _guard = CACHE_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let config = Config::new().unwrap();
+        let result = GeoLookup::lookup_ip("8.8.8.8", &config).await;
         assert!(result.is_ok());
         let geo_info = result.unwrap();
         assert!(!geo_info.country.is_empty());
@@ -69,6 +368,7 @@ mod tests {
     }
     #[test]
     fn test_cache() {
+        let _guard = CACHE_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
         let test_info = crate::models::IpGeoInfo {
             country: "Test Country".to_string(),
             city: Some("Test City".to_string()),
@@ -77,9 +377,47 @@ mod tests {
             asn: None,
             organization: None,
         };
-        IP_CACHE.lock().unwrap().insert("1.1.1.1".to_string(), test_info);
+        IP_CACHE.lock().unwrap().put("1.1.1.1".to_string(), (test_info, Instant::now()));
+        assert_eq!(GeoLookup::cache_size(), 1);
+        GeoLookup::clear_cache();
+        assert_eq!(GeoLookup::cache_size(), 0);
+    }
+    #[test]
+    fn test_cache_eviction_at_capacity() {
+        let _guard = CACHE_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        resize_cache(1);
+        let info = |name: &str| crate::models::IpGeoInfo {
+            country: name.to_string(),
+            city: None,
+            latitude: 0.0,
+            longitude: 0.0,
+            asn: None,
+            organization: None,
+        };
+        IP_CACHE.lock().unwrap().put("2.2.2.2".to_string(), (info("a"), Instant::now()));
+        IP_CACHE.lock().unwrap().put("3.3.3.3".to_string(), (info("b"), Instant::now()));
+        // Capacity of 1 evicts the least-recently-used entry.
         assert_eq!(GeoLookup::cache_size(), 1);
+        assert!(get_fresh("3.3.3.3", Duration::from_secs(60)).is_some());
+        assert!(get_fresh("2.2.2.2", Duration::from_secs(60)).is_none());
+        resize_cache(10_000);
         GeoLookup::clear_cache();
+    }
+    #[test]
+    fn test_cache_expiry() {
+        let _guard = CACHE_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let info = crate::models::IpGeoInfo {
+            country: "Stale".to_string(),
+            city: None,
+            latitude: 0.0,
+            longitude: 0.0,
+            asn: None,
+            organization: None,
+        };
+        IP_CACHE.lock().unwrap().put("4.4.4.4".to_string(), (info, Instant::now()));
+        // A TTL of zero means every entry is immediately stale.
+        assert!(get_fresh("4.4.4.4", Duration::from_secs(0)).is_none());
         assert_eq!(GeoLookup::cache_size(), 0);
+        GeoLookup::clear_cache();
     }
 }
@@ -2,17 +2,26 @@
 //!
 //! This library provides the core functionality for DMARCer, including configuration,
 //! error handling, data models, file extraction, XML parsing, geolocation, webhook communication,
-//! and additional file handling utilities.
+//! content-addressed report caching, and additional file handling utilities.
 
 pub mod config;
 pub mod error;
 pub mod models;
 pub mod zip_handler;
 pub mod xml_parser;
+pub mod forensic_parser;
 pub mod geo;
 pub mod webhook;
 pub mod file_handlers;
+pub mod mailbox;
+pub mod tlsrpt;
+pub mod alerting;
+pub mod cache;
 
 pub use zip_handler::extract_zip;
 pub use xml_parser::parse_dmarc_xml;
+pub use forensic_parser::parse_forensic_report;
+pub use mailbox::fetch_unread_attachments;
+pub use tlsrpt::parse_tlsrpt_json;
+pub use cache::parse_dmarc_xml_cached;
 pub use config::Config;
@@ -11,21 +11,85 @@ mod error;
 mod models;
 mod zip_handler;
 mod xml_parser;
+mod forensic_parser;
 mod geo;
 mod webhook;
 mod file_handlers;
+mod mailbox;
+mod tlsrpt;
+mod alerting;
+mod cache;
 
 use clap::Parser;
 use colored::*;
 use config::Config;
 use zip_handler::extract_zip;
-use crate::xml_parser::parse_dmarc_xml;
+use crate::cache::parse_dmarc_xml_cached;
 use anyhow::{Result, Context};
 use serde::{Deserialize, Serialize};
 use prettytable::{Table, Row, Cell, row};
 use std::path::PathBuf;
 use std::str::FromStr;
-use models::DmarcRecord;
+use models::{DateRange, DkimResult, DmarcPolicy, DmarcRecord, IpGeoInfo, PolicyEvaluated, ReportMetadata, SpfResult};
+use std::collections::HashMap;
+
+/// JSON output envelope: the records alongside the report metadata and published
+/// policy they were parsed from, so `--output json` doesn't silently drop them.
+#[derive(Debug, Serialize)]
+struct ReportOutput {
+    metadata: Option<ReportMetadata>,
+    policy: Option<DmarcPolicy>,
+    records: Vec<EnrichedRecord>,
+}
+
+/// A [`DmarcRecord`] paired with the [`IpGeoInfo`] resolved for its `source_ip`,
+/// for output only; `DmarcRecord` itself stays a pure parse result so parsing
+/// doesn't depend on geolocation. The `geo_*` fields are `IpGeoInfo` flattened
+/// onto scalar columns (rather than a nested `geo` struct) since `csv::Writer`
+/// can't serialize a nested struct into a record; they're `None` when
+/// geolocation isn't configured (no `DMARC_MMDB_PATH`) or the lookup for that
+/// particular IP failed.
+#[derive(Debug, Serialize)]
+struct EnrichedRecord {
+    source_ip: String,
+    count: u32,
+    policy_evaluated: PolicyEvaluated,
+    header_from: String,
+    envelope_from: Option<String>,
+    envelope_to: Option<String>,
+    dkim: Vec<DkimResult>,
+    spf: SpfResult,
+    date_range: DateRange,
+    geo_country: Option<String>,
+    geo_city: Option<String>,
+    geo_latitude: Option<f64>,
+    geo_longitude: Option<f64>,
+    geo_asn: Option<String>,
+    geo_organization: Option<String>,
+}
+
+impl EnrichedRecord {
+    fn new(record: DmarcRecord, geo_by_ip: &HashMap<String, IpGeoInfo>) -> Self {
+        let geo = geo_by_ip.get(&record.source_ip);
+        Self {
+            source_ip: record.source_ip,
+            count: record.count,
+            policy_evaluated: record.policy_evaluated,
+            header_from: record.header_from,
+            envelope_from: record.envelope_from,
+            envelope_to: record.envelope_to,
+            dkim: record.dkim,
+            spf: record.spf,
+            date_range: record.date_range,
+            geo_country: geo.map(|g| g.country.clone()),
+            geo_city: geo.and_then(|g| g.city.clone()),
+            geo_latitude: geo.map(|g| g.latitude),
+            geo_longitude: geo.map(|g| g.longitude),
+            geo_asn: geo.and_then(|g| g.asn.clone()),
+            geo_organization: geo.and_then(|g| g.organization.clone()),
+        }
+    }
+}
 
 /// CLI arguments for DMARCer.
 #[derive(Parser, Debug)]
@@ -39,9 +103,14 @@ use models::DmarcRecord;
     usage = "dmarcer <FILE> [OPTIONS]"
 )]
 struct Cli {
-    /// Path to DMARC ZIP report
-    #[arg(value_parser)]
-    file: PathBuf,
+    /// Path to DMARC ZIP report. Required unless `--mailbox` is given.
+    #[arg(value_parser, required_unless_present = "mailbox")]
+    file: Option<PathBuf>,
+
+    /// Instead of a local file, pull unread report attachments from the IMAP
+    /// mailbox configured via `DMARC_IMAP_*` and process each of them.
+    #[arg(long)]
+    mailbox: bool,
 
     /// Output format: table, csv, json
     #[arg(short, long, default_value = "table")]
@@ -110,34 +179,129 @@ async fn main() -> Result<()> {
         "Extracting, parsing & analyzing DMARC data".dimmed()
     );
 
-    log::info!("Processing file: {}", cli.file.display());
     let config = Config::new().context("Failed to load configuration")?;
 
-    let extracted_files = extract_zip(&cli.file, &config)
+    if cli.mailbox {
+        let attachments = mailbox::fetch_unread_attachments(&config)
+            .context("Failed to fetch unread mailbox attachments")?;
+
+        if attachments.is_empty() {
+            println!("{}", "No unread DMARC report attachments found.".yellow());
+            return Ok(());
+        }
+
+        for attachment in &attachments {
+            log::info!("Processing mailbox attachment: {}", attachment.filename);
+            let suffix = staged_suffix(&attachment.filename);
+            let mut staged = tempfile::Builder::new()
+                .suffix(&suffix)
+                .tempfile()
+                .context("Failed to create a temp file for a mailbox attachment")?;
+            std::io::Write::write_all(&mut staged, &attachment.contents)
+                .context("Failed to stage a mailbox attachment to disk")?;
+
+            process_path(staged.path(), &config, &cli.output).await?;
+        }
+
+        log::info!("{}", "Analysis complete!".bold().cyan());
+        return Ok(());
+    }
+
+    let file = cli.file.as_ref().expect("clap enforces file unless --mailbox is set");
+    log::info!("Processing file: {}", file.display());
+    process_path(file, &config, &cli.output).await?;
+
+    log::info!("{}", "Analysis complete!".bold().cyan());
+    Ok(())
+}
+
+/// Runs the extract/parse/print/webhook pipeline against a single report file,
+/// whether it came from the CLI's positional argument or was staged from a
+/// mailbox attachment.
+async fn process_path(path: &std::path::Path, config: &Config, output: &OutputFormat) -> Result<()> {
+    let is_forensic = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case("eml") || ext.eq_ignore_ascii_case("msg"))
+        .unwrap_or(false);
+
+    if is_forensic {
+        return print_forensic_report(&path.to_path_buf(), output);
+    }
+
+    if is_tlsrpt_path(path) {
+        return print_tlsrpt_report(path, config, output);
+    }
+
+    let extracted_files = extract_zip(path, config)
+        .await
         .context("Failed to extract file")?;
 
     let mut results = Vec::new();
     let mut policy_info = None;
+    let mut metadata_info = None;
 
     for xml in &extracted_files {
-        let (records, policy) = parse_dmarc_xml(xml)
+        let (records, policy, metadata) = parse_dmarc_xml_cached(xml, config)
             .context("Failed to parse DMARC XML")?;
         results.extend(records);
         policy_info = Some(policy);
+        // A cache hit skips the parse and comes back without metadata; keep
+        // whatever we already had rather than clobbering it with `None`.
+        if metadata.is_some() {
+            metadata_info = metadata;
+        }
     }
 
-    match cli.output {
+    // Geolocation runs only when a local MaxMind-style database is configured
+    // via `DMARC_MMDB_PATH`; when it isn't set we skip enrichment entirely
+    // rather than sending every IP to `geo::GeoLookup`'s online IP-API path.
+    // With it set, an IP that has no record in the database is still looked
+    // up online through IP-API, one request per such IP.
+    let geo_by_ip = if config.mmdb_path.is_some() {
+        let distinct_ips: Vec<String> = {
+            let mut ips: Vec<String> = results.iter().map(|r| r.source_ip.clone()).collect();
+            ips.sort();
+            ips.dedup();
+            ips
+        };
+        geo::GeoLookup::lookup_ips(&distinct_ips, config)
+            .await
+            .unwrap_or_else(|e| {
+                log::warn!("Geolocation enrichment failed: {}", e);
+                HashMap::new()
+            })
+    } else {
+        HashMap::new()
+    };
+
+    match output {
         OutputFormat::Json => {
-            println!("{}", serde_json::to_string_pretty(&results)?);
+            let records = results.iter().cloned().map(|r| EnrichedRecord::new(r, &geo_by_ip)).collect();
+            let report = ReportOutput { metadata: metadata_info, policy: policy_info, records };
+            println!("{}", serde_json::to_string_pretty(&report)?);
         }
         OutputFormat::Csv => {
             let mut wtr = csv::Writer::from_writer(std::io::stdout());
             for record in &results {
-                wtr.serialize(record)?;
+                wtr.serialize(EnrichedRecord::new(record.clone(), &geo_by_ip))?;
             }
             wtr.flush()?;
         }
         OutputFormat::Table => {
+            if let Some(metadata) = metadata_info.as_ref() {
+                println!("{}", "Report Metadata".bold().blue());
+                println!("{}", "----------------------------".dimmed());
+                println!("{}: {} <{}>", "Reported by".bold(), metadata.org_name, metadata.email);
+                println!("{}: {}", "Report ID".bold(), metadata.report_id);
+                println!(
+                    "{}: {} - {}\n",
+                    "Reporting Window".bold(),
+                    metadata.date_range.begin,
+                    metadata.date_range.end
+                );
+            }
+
             if let Some(policy) = policy_info.as_ref() {
                 println!("{}", "DMARC Policy Information".bold().blue());
                 println!("{}", "----------------------------".dimmed());
@@ -145,13 +309,14 @@ async fn main() -> Result<()> {
                 println!("{}: {}", "SPF Alignment".bold(), policy.aspf);
                 println!("{}: {}", "DKIM Alignment".bold(), policy.adkim);
                 println!("{}: {}", "Policy".bold(), policy.policy);
+                println!("{}: {}", "Subdomain Policy".bold(), policy.sp);
                 println!("{}: {}\n", "Percentage Applied".bold(), policy.pct);
             }
 
             if !results.is_empty() {
                 let mut table = Table::new();
-                table.add_row(row!["Source IP", "Count", "SPF", "DKIM"]);
-                
+                table.add_row(row!["Source IP", "Count", "SPF", "DKIM", "Country", "ASN"]);
+
                 for record in &results {
                     let spf_str = format_spf(&record.spf);
                     let dkim_results: Vec<String> = record.dkim.iter()
@@ -163,15 +328,20 @@ async fn main() -> Result<()> {
                     } else {
                         dkim_results.join(", ")
                     };
-                    
+                    let geo = geo_by_ip.get(&record.source_ip);
+                    let country_str = geo.map(|g| g.country.as_str()).unwrap_or("unknown");
+                    let asn_str = geo.and_then(|g| g.asn.as_deref()).unwrap_or("unknown");
+
                     table.add_row(Row::new(vec![
                         Cell::new(&record.source_ip),
                         Cell::new(&record.count.to_string()),
                         Cell::new(&spf_str),
                         Cell::new(&dkim_str),
+                        Cell::new(country_str),
+                        Cell::new(asn_str),
                     ]));
                 }
-                
+
                 table.printstd();
             } else {
                 println!("{}", "No DMARC records found.".yellow());
@@ -181,25 +351,227 @@ async fn main() -> Result<()> {
 
     if let Some(url) = &config.webhook_url {
         log::info!("Sending results to webhook: {}", url);
-        send_webhook(url, &results).await?;
+        send_webhook(url, results.clone(), policy_info.clone().unwrap_or_default(), config).await?;
+    }
+
+    if !config.alert_webhook_urls.is_empty() {
+        let evaluator = alerting::AlertEvaluator::new(alerting::AlertRules::from_config(config));
+        // No persisted history of prior source IPs is available in the one-shot
+        // CLI path, so the new-source-IP rule is skipped here (`None`); the worker
+        // is long-lived and backs this same rule with a Redis-persisted IP set
+        // (see `worker::evaluate_and_dispatch_alerts`).
+        if let Some(payload) = evaluator.evaluate(&results, None) {
+            log::warn!("DMARC alert triggered: {:?}", payload.kinds);
+            let dispatcher = alerting::AlertDispatcher::new(
+                &config.alert_webhook_urls,
+                std::time::Duration::from_secs(config.alert_timeout_secs),
+                config.alert_max_retries,
+            )?;
+            dispatcher.dispatch(&payload).await?;
+        }
     }
 
-    log::info!("{}", "Analysis complete!".bold().cyan());
     Ok(())
 }
 
-/// Sends webhook data if a webhook URL is configured.
-async fn send_webhook(url: &str, results: &[DmarcRecord]) -> Result<()> {
-    let client = reqwest::Client::new();
-    client
-        .post(url)
-        .json(results)
-        .send()
-        .await
-        .context("Failed to send webhook")?;
+/// Reads and renders a forensic/failure report (`.eml`/`.msg`) in the requested
+/// output format, alongside (rather than mixed into) the aggregate record formats.
+fn print_forensic_report(path: &PathBuf, output: &OutputFormat) -> Result<()> {
+    let raw = std::fs::read_to_string(path).context("Failed to read forensic report file")?;
+    let report = forensic_parser::parse_forensic_report(&raw)
+        .context("Failed to parse forensic report")?;
+
+    match output {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        OutputFormat::Csv => {
+            let mut wtr = csv::Writer::from_writer(std::io::stdout());
+            wtr.serialize(&report)?;
+            wtr.flush()?;
+        }
+        OutputFormat::Table => {
+            println!("{}", "Forensic Report".bold().blue());
+            println!("{}", "----------------------------".dimmed());
+            println!("{}: {}", "Feedback Type".bold(), report.feedback_type);
+            println!("{}: {}", "Source IP".bold(), report.source_ip.as_deref().unwrap_or("unknown"));
+            println!("{}: {}", "Reported Domain".bold(), report.reported_domain.as_deref().unwrap_or("unknown"));
+            println!("{}: {}", "Delivery Result".bold(), report.delivery_result.as_deref().unwrap_or("unknown"));
+            println!(
+                "{}: {}",
+                "Auth Failure".bold(),
+                if report.auth_failure.is_empty() { "none reported".to_string() } else { report.auth_failure.join(", ") }
+            );
+            if let Some(auth_results) = &report.authentication_results {
+                println!("{}: {}", "Authentication-Results".bold(), auth_results);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A TLS-RPT report (RFC 8460) is always named `....json` or `....json.gz`
+/// (the latter being the common case for real-world reports).
+fn is_tlsrpt_path(path: &std::path::Path) -> bool {
+    let lower = path.to_string_lossy().to_lowercase();
+    lower.ends_with(".json") || lower.ends_with(".json.gz")
+}
+
+/// Picks the suffix a mailbox attachment should be staged to disk under, so
+/// `is_tlsrpt_path` and `extract_zip`'s extension fallback see the same format
+/// `filename` names. `.extension()` only ever returns the last dotted
+/// component, which would truncate `report.json.gz` down to a bare `.gz` and
+/// send a gzip-compressed TLS-RPT report into the DMARC XML pipeline instead;
+/// special-case that one compound extension before falling back to the
+/// single-extension default every other attachment uses.
+fn staged_suffix(filename: &str) -> String {
+    if filename.to_lowercase().ends_with(".json.gz") {
+        return ".json.gz".to_string();
+    }
+    std::path::Path::new(filename)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| format!(".{}", ext))
+        .unwrap_or_default()
+}
+
+/// Reads and renders a TLS-RPT report (plain or gzip-compressed JSON) in the
+/// requested output format, alongside the DMARC aggregate and forensic paths.
+fn print_tlsrpt_report(path: &std::path::Path, config: &Config, output: &OutputFormat) -> Result<()> {
+    let handler = file_handlers::FileHandler::new(config.clone());
+    let report = handler
+        .process_tlsrpt_file(path)
+        .context("Failed to parse TLS-RPT report")?;
+
+    match output {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        OutputFormat::Csv => {
+            let mut wtr = csv::Writer::from_writer(std::io::stdout());
+            for policy in &report.policies {
+                wtr.serialize(policy)?;
+            }
+            wtr.flush()?;
+        }
+        OutputFormat::Table => {
+            println!("{}", "TLS-RPT Report".bold().blue());
+            println!("{}", "----------------------------".dimmed());
+            println!("{}: {}", "Organization".bold(), report.organization_name);
+            println!("{}: {}", "Report ID".bold(), report.report_id);
+            println!(
+                "{}: {} - {}\n",
+                "Reporting Window".bold(),
+                report.date_range.start_datetime,
+                report.date_range.end_datetime
+            );
+
+            let mut table = Table::new();
+            table.add_row(row!["Policy Domain", "Policy Type", "MX Hosts", "Successful", "Failed"]);
+            for policy_result in &report.policies {
+                table.add_row(Row::new(vec![
+                    Cell::new(&policy_result.policy.policy_domain),
+                    Cell::new(&policy_result.policy.policy_type.to_string()),
+                    Cell::new(&policy_result.policy.mx_host.join(", ")),
+                    Cell::new(&policy_result.summary.total_successful_session_count.to_string()),
+                    Cell::new(&policy_result.summary.total_failure_session_count.to_string()),
+                ]));
+            }
+            table.printstd();
+
+            for policy_result in &report.policies {
+                if policy_result.failure_details.is_empty() {
+                    continue;
+                }
+                println!(
+                    "\n{} {}",
+                    "Failure details for".bold().red(),
+                    policy_result.policy.policy_domain
+                );
+                for detail in &policy_result.failure_details {
+                    println!(
+                        "  {} ({} session{}){}",
+                        detail.result_type,
+                        detail.failed_session_count,
+                        if detail.failed_session_count == 1 { "" } else { "s" },
+                        detail
+                            .receiving_mx_hostname
+                            .as_deref()
+                            .map(|h| format!(" on {}", h))
+                            .unwrap_or_default()
+                    );
+                }
+            }
+        }
+    }
+
     Ok(())
 }
 
+/// Sends webhook data if a webhook URL is configured.
+///
+/// Delivery goes through [`webhook::WebhookHandler`] so the authenticator and
+/// compression mode selected via `config.webhook_auth_mode` /
+/// `config.webhook_compression` are actually applied to the request, not just
+/// exercised by `webhook`'s own unit tests.
+async fn send_webhook(url: &str, results: Vec<DmarcRecord>, policy: DmarcPolicy, config: &Config) -> Result<()> {
+    let handler = webhook::WebhookHandler::new(
+        url,
+        std::time::Duration::from_secs(config.webhook_timeout),
+        3,
+        build_webhook_authenticator(config)?,
+        build_webhook_compression(config),
+    )?;
+    handler.send(results, policy).await
+}
+
+/// Builds the [`webhook::Authenticator`] selected by `config.webhook_auth_mode`.
+fn build_webhook_authenticator(config: &Config) -> Result<std::sync::Arc<dyn webhook::Authenticator>> {
+    match config.webhook_auth_mode.as_str() {
+        "none" => Ok(std::sync::Arc::new(webhook::NoAuth)),
+        "bearer" => {
+            let token = config
+                .webhook_auth_token
+                .as_deref()
+                .context("DMARC_WEBHOOK_AUTH_TOKEN is required when webhook_auth_mode is \"bearer\"")?;
+            Ok(std::sync::Arc::new(webhook::BearerAuth::new(token)))
+        }
+        "header" => {
+            let name = config
+                .webhook_auth_header_name
+                .as_deref()
+                .context("DMARC_WEBHOOK_AUTH_HEADER_NAME is required when webhook_auth_mode is \"header\"")?;
+            let value = config
+                .webhook_auth_header_value
+                .as_deref()
+                .context("DMARC_WEBHOOK_AUTH_HEADER_VALUE is required when webhook_auth_mode is \"header\"")?;
+            Ok(std::sync::Arc::new(webhook::HeaderAuth::new(name, value)))
+        }
+        "hmac" => {
+            let secret = config
+                .webhook_auth_token
+                .as_deref()
+                .context("DMARC_WEBHOOK_AUTH_TOKEN is required when webhook_auth_mode is \"hmac\"")?;
+            Ok(std::sync::Arc::new(webhook::HmacSha256Auth::new(
+                secret.as_bytes().to_vec(),
+                config.webhook_hmac_signature_header.clone(),
+            )))
+        }
+        other => Err(anyhow::anyhow!("Unknown webhook_auth_mode: {}", other)),
+    }
+}
+
+/// Builds the [`webhook::WebhookCompression`] selected by `config.webhook_compression`.
+fn build_webhook_compression(config: &Config) -> webhook::WebhookCompression {
+    let threshold_bytes = config.webhook_compression_threshold_bytes;
+    match config.webhook_compression.as_str() {
+        "gzip" => webhook::WebhookCompression::Gzip { threshold_bytes },
+        "deflate" => webhook::WebhookCompression::Deflate { threshold_bytes },
+        _ => webhook::WebhookCompression::Disabled,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -211,4 +583,11 @@ mod tests {
         assert!(matches!(OutputFormat::from_str("json"), Ok(OutputFormat::Json)));
         assert!(OutputFormat::from_str("invalid").is_err());
     }
+
+    #[test]
+    fn test_staged_suffix_preserves_compound_tlsrpt_extension() {
+        assert_eq!(staged_suffix("report.json.gz"), ".json.gz");
+        assert_eq!(staged_suffix("report.json"), ".json");
+        assert_eq!(staged_suffix("report.zip"), ".zip");
+    }
 }
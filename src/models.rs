@@ -12,6 +12,14 @@ pub struct DmarcPolicy {
     pub adkim: AlignmentMode,
     pub aspf: AlignmentMode,
     pub policy: PolicyType,
+    /// Subdomain policy (`sp`); falls back to `policy` when the report omits it.
+    pub sp: PolicyType,
+    /// Requested failure-reporting options (`fo`), e.g. `"0"`, `"1"`, or `"d:s"`.
+    pub fo: String,
+    /// Requested report format(s) (`rf`), e.g. `"afrf"`.
+    pub rf: String,
+    /// Requested aggregate report interval in seconds (`ri`).
+    pub ri: u32,
     pub pct: u8,
 }
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -21,6 +29,8 @@ pub struct DmarcRecord {
     pub policy_evaluated: PolicyEvaluated,
     pub header_from: String,
     pub envelope_from: Option<String>,
+    /// `<envelope_to>` from the record's `<identifiers>` block, when present.
+    pub envelope_to: Option<String>,
     pub dkim: Vec<DkimResult>,
     pub spf: SpfResult,
     pub date_range: DateRange,
@@ -30,6 +40,47 @@ pub struct PolicyEvaluated {
     pub disposition: String,
     pub dkim: DkimVerdict,
     pub spf: SpfVerdict,
+    /// `<reason>` overrides explaining why the evaluated disposition differs
+    /// from what the published policy alone would dictate.
+    pub reasons: Vec<PolicyOverrideReason>,
+}
+/// A single `<reason>` entry under `<policy_evaluated>`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PolicyOverrideReason {
+    #[serde(rename = "type")]
+    pub reason_type: String,
+    pub comment: Option<String>,
+}
+/// A parsed DMARC forensic/failure report (AFRF, RFC 6591): the per-message
+/// feedback carried by the `ruf` stream rather than the daily aggregate XML.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ForensicReport {
+    /// Always `"auth-failure"` for DMARC forensic reports, from `Feedback-Type`.
+    pub feedback_type: String,
+    pub user_agent: Option<String>,
+    pub version: Option<String>,
+    pub original_mail_from: Option<String>,
+    pub source_ip: Option<String>,
+    pub reported_domain: Option<String>,
+    pub delivery_result: Option<String>,
+    /// One entry per `Auth-Failure` header (e.g. `"dkim"`, `"spf"`); a report
+    /// may fail both mechanisms.
+    pub auth_failure: Vec<String>,
+    pub authentication_results: Option<String>,
+    /// Raw headers of the offending message, from the `message/rfc822` (or
+    /// `text/rfc822-headers`) part that follows the feedback report.
+    pub original_headers: Option<String>,
+}
+
+/// `<report_metadata>`: who sent the report, its ID, and the reporting window.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ReportMetadata {
+    pub org_name: String,
+    pub email: String,
+    pub extra_contact_info: Option<String>,
+    pub report_id: String,
+    pub date_range: DateRange,
+    pub errors: Vec<String>,
 }
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DkimResult {
@@ -43,7 +94,7 @@ pub struct SpfResult {
     pub scope: String,
     pub result: SpfVerdict,
 }
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct DateRange {
     pub begin: i64,
     pub end: i64,
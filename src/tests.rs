@@ -7,9 +7,9 @@ mod tests {
     use super::*;
     use crate::zip_handler::extract_zip;
     use crate::xml_parser::parse_dmarc_xml;
-    #[test]
-    fn test_zip_extraction() {
-        let result = extract_zip("test.zip", &crate::Config::new().unwrap());
+    #[tokio::test]
+    async fn test_zip_extraction() {
+        let result = extract_zip("test.zip", &crate::Config::new().unwrap()).await;
         assert!(result.is_ok());
     }
     #[test]
@@ -19,11 +19,50 @@ mod tests {
             <record>
                 <source_ip>1.2.3.4</source_ip>
                 <count>1</count>
-                <header_from>example.com</header_from>
+                <identifiers>
+                    <header_from>example.com</header_from>
+                    <envelope_from>sender.example.com</envelope_from>
+                    <envelope_to>recipient.example.com</envelope_to>
+                </identifiers>
             </record>
         </feedback>
         "#;
         let result = parse_dmarc_xml(xml_data);
         assert!(result.is_ok());
     }
+    /// `header_from`/`envelope_from`/`envelope_to` live under `<identifiers>` per
+    /// RFC 7489's aggregate report schema; verify they actually populate from
+    /// that nested shape rather than just checking `.is_ok()`.
+    #[test]
+    fn test_xml_parsing_populates_identifiers_and_metadata() {
+        let xml_data = r#"
+        <feedback>
+            <report_metadata>
+                <org_name>example.org</org_name>
+                <email>noreply@example.org</email>
+                <report_id>1234567890</report_id>
+                <date_range>
+                    <begin>1609459200</begin>
+                    <end>1609545600</end>
+                </date_range>
+            </report_metadata>
+            <record>
+                <source_ip>1.2.3.4</source_ip>
+                <count>1</count>
+                <identifiers>
+                    <header_from>example.com</header_from>
+                    <envelope_from>sender.example.com</envelope_from>
+                    <envelope_to>recipient.example.com</envelope_to>
+                </identifiers>
+            </record>
+        </feedback>
+        "#;
+        let (records, _policy, metadata) = parse_dmarc_xml(xml_data).unwrap();
+        assert_eq!(metadata.org_name, "example.org");
+        assert_eq!(metadata.report_id, "1234567890");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].header_from, "example.com");
+        assert_eq!(records[0].envelope_from.as_deref(), Some("sender.example.com"));
+        assert_eq!(records[0].envelope_to.as_deref(), Some("recipient.example.com"));
+    }
 }
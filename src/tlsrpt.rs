@@ -0,0 +1,163 @@
+//! SMTP TLS Reporting (TLS-RPT) Parser, RFC 8460
+//!
+//! Operators who deploy MTA-STS or DANE/TLSA receive daily TLS-RPT reports from
+//! sending MTAs, delivered as a single JSON document (often gzip-compressed,
+//! conventionally named like `example.com!reporter.example!...!....json.gz`).
+//! Unlike DMARC aggregate reports, TLS-RPT is JSON rather than XML, so this
+//! module parses it directly with `serde_json` instead of `xml_parser`'s
+//! event-based approach.
+
+use crate::error::{DmarcError, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A full TLS-RPT report: who sent it, the reporting window, and one entry per
+/// policy domain covered by the report.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct TlsRptReport {
+    pub organization_name: String,
+    pub date_range: TlsRptDateRange,
+    pub contact_info: Option<String>,
+    pub report_id: String,
+    pub policies: Vec<TlsRptPolicyResult>,
+}
+
+/// `date-range`: the reporting window, as the RFC 3339 timestamps TLS-RPT uses
+/// (unlike DMARC aggregate's Unix-epoch `date_range`).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct TlsRptDateRange {
+    pub start_datetime: String,
+    pub end_datetime: String,
+}
+
+/// One `policies[]` entry: the policy that was evaluated, plus the aggregate
+/// success/failure counts and failure details observed against it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct TlsRptPolicyResult {
+    pub policy: TlsRptPolicy,
+    pub summary: TlsRptSummary,
+    #[serde(default)]
+    pub failure_details: Vec<TlsRptFailureDetail>,
+}
+
+/// The `policy` object identifying which policy (MTA-STS, DANE/TLSA, or none)
+/// the sending MTA evaluated for this domain.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct TlsRptPolicy {
+    pub policy_type: TlsRptPolicyType,
+    #[serde(default)]
+    pub policy_string: Vec<String>,
+    pub policy_domain: String,
+    #[serde(default)]
+    pub mx_host: Vec<String>,
+}
+
+/// The kind of policy a sending MTA evaluated against the receiving domain.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum TlsRptPolicyType {
+    Sts,
+    Tlsa,
+    #[default]
+    NoPolicyFound,
+}
+
+/// `summary`: the total number of sessions that succeeded or failed negotiating
+/// TLS under this policy during the reporting window.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct TlsRptSummary {
+    pub total_successful_session_count: u64,
+    pub total_failure_session_count: u64,
+}
+
+/// One `failure-details[]` entry: a specific reason sessions failed, and how
+/// many sessions failed for that reason.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct TlsRptFailureDetail {
+    pub result_type: String,
+    pub sending_mta_ip: Option<String>,
+    pub receiving_mx_hostname: Option<String>,
+    pub receiving_mx_helo: Option<String>,
+    pub receiving_ip: Option<String>,
+    pub failed_session_count: u64,
+    pub additional_information: Option<String>,
+    pub failure_reason_code: Option<String>,
+}
+
+impl fmt::Display for TlsRptPolicyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsRptPolicyType::Sts => write!(f, "sts"),
+            TlsRptPolicyType::Tlsa => write!(f, "tlsa"),
+            TlsRptPolicyType::NoPolicyFound => write!(f, "no-policy-found"),
+        }
+    }
+}
+
+/// Parses a raw TLS-RPT JSON document (already decompressed, if it arrived gzipped).
+pub fn parse_tlsrpt_json(raw: &str) -> Result<TlsRptReport> {
+    serde_json::from_str(raw).map_err(|e| DmarcError::Format(format!("Invalid TLS-RPT report: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"{
+        "organization-name": "Example Inc.",
+        "date-range": {
+            "start-datetime": "2026-07-01T00:00:00Z",
+            "end-datetime": "2026-07-01T23:59:59Z"
+        },
+        "contact-info": "tlsrpt@example.com",
+        "report-id": "2026-07-01T00:00:00Z_example.com",
+        "policies": [
+            {
+                "policy": {
+                    "policy-type": "sts",
+                    "policy-string": ["version: STSv1", "mode: enforce"],
+                    "policy-domain": "example.com",
+                    "mx-host": ["mx1.example.com", "mx2.example.com"]
+                },
+                "summary": {
+                    "total-successful-session-count": 5326,
+                    "total-failure-session-count": 1
+                },
+                "failure-details": [
+                    {
+                        "result-type": "certificate-expired",
+                        "sending-mta-ip": "198.51.100.62",
+                        "receiving-mx-hostname": "mx1.example.com",
+                        "failed-session-count": 1
+                    }
+                ]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_tlsrpt_json() {
+        let report = parse_tlsrpt_json(SAMPLE).unwrap();
+        assert_eq!(report.organization_name, "Example Inc.");
+        assert_eq!(report.policies.len(), 1);
+        let policy = &report.policies[0];
+        assert_eq!(policy.policy.policy_type, TlsRptPolicyType::Sts);
+        assert_eq!(policy.policy.policy_domain, "example.com");
+        assert_eq!(policy.summary.total_successful_session_count, 5326);
+        assert_eq!(policy.summary.total_failure_session_count, 1);
+        assert_eq!(policy.failure_details.len(), 1);
+        assert_eq!(policy.failure_details[0].result_type, "certificate-expired");
+    }
+
+    #[test]
+    fn test_parse_tlsrpt_json_invalid() {
+        let result = parse_tlsrpt_json("not json");
+        assert!(result.is_err());
+    }
+}
@@ -4,26 +4,125 @@
 //! processes it, and stores the results in PostgreSQL using parameterized queries (to prevent SQL injection).
 //! Finally, it publishes a notification via Redis Pub/Sub.
 //!
-//! **Important:**  
+//! **Important:**
 //! - Ensure that the environment variable `DATABASE_URL` is set (or run `cargo sqlx prepare`) so that
-//!   SQLX's compile‑time query checking works.  
+//!   SQLX's compile‑time query checking works.
 //! - The worker uses `get_multiplexed_async_connection` for Redis to avoid deprecation warnings.
+//!
+//! **Hot-reloadable configuration:**
+//! - Security limits (`max_decompressed_size`, `max_compression_ratio`, `max_files_in_zip`, etc.)
+//!   live behind a shared `Arc<ArcSwap<Config>>` instead of being read once at startup. Sending
+//!   `SIGHUP` to the worker process re-reads the environment (plus `DMARC_CONFIG_FILE` if set) and
+//!   atomically swaps in the new `Config` between message deliveries, so operators can tighten
+//!   ZIP-bomb limits in response to an ongoing attack without downtime. A reload that fails
+//!   validation leaves the previous config in place.
+//!
+//! **Alerting:**
+//! - After a report's records are persisted, they're evaluated against the configured
+//!   `DMARC_ALERT_*` thresholds, backed by a Redis set (`dmarc:known_source_ips`) of
+//!   every source IP seen so far so the new-source-IP rule actually fires here — the
+//!   one-shot CLI path has no such history and skips that rule. A tripped alert is
+//!   dispatched to the configured sinks; any failure along the way is logged and
+//!   does not fail the delivery.
+//!
+//! **Resilient delivery handling:**
+//! - Each delivery is processed in its own task, isolating a single malformed report or
+//!   transient DB hiccup from the rest of the consumer. A permanent failure (the report
+//!   doesn't parse) is nacked without requeue; a transient failure (DB/Redis/AMQP hiccups)
+//!   is republished to `dmarc_processing.retry` with an exponentially increasing
+//!   per-message TTL and an `x-retry-count` header, so RabbitMQ dead-letters it back onto
+//!   `dmarc_processing` once the backoff elapses. After `MAX_RETRIES` retries the message
+//!   is nacked without requeue and `dmarc_processing`'s own dead-letter-exchange routes it
+//!   to `dmarc_processing.dead` instead of looping forever.
 
 use anyhow::Result;
+use arc_swap::ArcSwap;
 use futures_util::stream::StreamExt;
 use lapin::{
-    options::{BasicAckOptions, BasicConsumeOptions, QueueDeclareOptions},
-    types::FieldTable,
-    Connection, ConnectionProperties,
+    options::{BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicPublishOptions, QueueDeclareOptions},
+    types::{AMQPValue, FieldTable, ShortString},
+    BasicProperties, Channel, Connection, ConnectionProperties,
 };
 use redis::AsyncCommands;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use std::env;
+use std::sync::Arc;
 use tokio;
+use tokio::signal::unix::{signal, SignalKind};
 
-use dmarcer::{parse_dmarc_xml};
+use dmarcer::alerting::{AlertDispatcher, AlertEvaluator, AlertRules};
+use dmarcer::error::DmarcError;
+use dmarcer::{extract_zip, parse_dmarc_xml, Config};
 use dmarcer::models::DmarcRecord;
+use std::collections::HashSet;
+
+/// Main processing queue; consumers read deliveries from here.
+const QUEUE_MAIN: &str = "dmarc_processing";
+/// Delayed-retry queue: messages sit here for their backoff TTL, then RabbitMQ
+/// dead-letters them back onto `QUEUE_MAIN` via this queue's own dlx arguments.
+const QUEUE_RETRY: &str = "dmarc_processing.retry";
+/// Final resting place for permanently failed or retry-exhausted messages.
+const QUEUE_DEAD: &str = "dmarc_processing.dead";
+/// Header tracking how many times a message has been retried.
+const HEADER_RETRY_COUNT: &str = "x-retry-count";
+/// Maximum number of transient-failure retries before a message is dead-lettered.
+const MAX_RETRIES: i32 = 5;
+/// Base delay for exponential backoff between retries.
+const RETRY_BASE_DELAY_MS: u64 = 1_000;
+
+/// Whether a processing failure is permanent (the report itself is bad, so retrying
+/// won't help) or transient (infrastructure hiccup, worth retrying with backoff).
+enum Failure {
+    Permanent(anyhow::Error),
+    Transient(anyhow::Error),
+}
+
+/// Classifies a processing error: malformed input (`Format`/`UnsupportedFile`/`Xml`/
+/// `Parse`/`FileTooLarge`) is permanent — retrying a zip bomb or an oversized archive
+/// just wastes `MAX_RETRIES` backoff cycles before it dead-letters anyway — while
+/// everything else (I/O, DB, Redis, AMQP) is treated as a transient infrastructure
+/// failure worth retrying.
+fn classify_error(e: anyhow::Error) -> Failure {
+    match e.downcast_ref::<DmarcError>() {
+        Some(DmarcError::Format(_))
+        | Some(DmarcError::UnsupportedFile(_))
+        | Some(DmarcError::Xml(_))
+        | Some(DmarcError::Parse(_))
+        | Some(DmarcError::FileTooLarge(_)) => Failure::Permanent(e),
+        _ => Failure::Transient(e),
+    }
+}
+
+/// Shared, hot-swappable configuration handle.
+type SharedConfig = Arc<ArcSwap<Config>>;
+
+/// Spawns a task that reloads `shared_config` from the environment (and `config_file`, if
+/// given) whenever the process receives `SIGHUP`. Reload failures are logged and the
+/// previously active config is retained.
+fn spawn_config_reload_task(shared_config: SharedConfig, config_file: Option<String>) {
+    tokio::spawn(async move {
+        let mut sighup = match signal(SignalKind::hangup()) {
+            Ok(s) => s,
+            Err(e) => {
+                eprintln!("Failed to install SIGHUP handler: {:?}", e);
+                return;
+            }
+        };
+        loop {
+            sighup.recv().await;
+            match Config::reload(config_file.as_deref()) {
+                Ok(new_config) => {
+                    shared_config.store(Arc::new(new_config));
+                    println!("Configuration reloaded from SIGHUP");
+                }
+                Err(e) => {
+                    eprintln!("Config reload failed, keeping previous config: {:?}", e);
+                }
+            }
+        }
+    });
+}
 
 /// Message format for tasks received from the MQ.
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,20 +136,35 @@ struct TaskMessage {
 
 /// Downloads the DMARC report file from storage.
 ///
-/// For demonstration purposes, this simply reads the file from a local path.
+/// For demonstration purposes, this simply resolves a local path.
 /// In a production environment, integrate with a MinIO/S3 client.
 async fn download_file(file_path: &str) -> Result<String> {
-    let content = tokio::fs::read_to_string(file_path).await?;
-    Ok(content)
+    Ok(file_path.to_string())
 }
 
-/// Processes the DMARC report file and returns the extracted DMARC records.
-///
-/// In this mode, the output is JSON-only (raw DMARC records) for further processing.
-async fn process_report(file_content: &str) -> Result<Vec<DmarcRecord>> {
-    // For compressed files, you would call extract_zip first.
-    // Here, we assume file_content is the XML report.
-    let (records, _policy) = parse_dmarc_xml(file_content)?;
+/// Extracts and parses the DMARC report at `file_path`, enforcing the security limits
+/// from the currently active `config` (so a hot-reloaded limit applies to the very next
+/// message, not just ones received after a restart). Also resolves every distinct
+/// `source_ip` through the batched, rate-limited geolocation lookup so the cache is
+/// warm by the time the records are persisted.
+async fn process_report(file_path: &str, config: Arc<Config>) -> Result<Vec<DmarcRecord>> {
+    let extracted = extract_zip(file_path, &config).await?;
+    let mut records = Vec::new();
+    for xml in &extracted {
+        let (recs, _policy, _metadata) = parse_dmarc_xml(xml)?;
+        records.extend(recs);
+    }
+
+    let distinct_ips: Vec<String> = records
+        .iter()
+        .map(|r| r.source_ip.clone())
+        .collect::<std::collections::HashSet<_>>()
+        .into_iter()
+        .collect();
+    if let Err(e) = dmarcer::geo::GeoLookup::lookup_ips(&distinct_ips, &config).await {
+        log::warn!("Geolocation warm-up failed for task: {:?}", e);
+    }
+
     Ok(records)
 }
 
@@ -92,7 +206,7 @@ async fn insert_records_to_postgres(
 }
 
 /// Publishes a notification on Redis Pub/Sub with the given task_id.
-/// 
+///
 /// Uses get_multiplexed_async_connection and explicitly annotates the publish call.
 async fn publish_notification(redis_client: &redis::Client, task_id: &str) -> Result<()> {
     let mut conn = redis_client.get_multiplexed_async_connection().await?;
@@ -101,6 +215,196 @@ async fn publish_notification(redis_client: &redis::Client, task_id: &str) -> Re
     Ok(())
 }
 
+/// Redis set tracking every source IP the worker has ever inserted, so
+/// [`evaluate_and_dispatch_alerts`] can tell a genuinely new sender apart from one
+/// seen in a prior report. Unlike the CLI (which processes one file in isolation and
+/// has no history to compare against), the worker is long-lived, making it the one
+/// caller that can actually back `AlertEvaluator`'s new-source-IP rule with real
+/// persisted state.
+const REDIS_KEY_KNOWN_SOURCE_IPS: &str = "dmarc:known_source_ips";
+
+/// Evaluates `records` against `config`'s alert rules, using the Redis-persisted set
+/// of previously seen source IPs for the new-source-IP rule, dispatches a payload to
+/// the configured sinks if one trips, then folds this batch's IPs into that set for
+/// next time. Alerting is a best-effort side channel: a failure here is logged and
+/// does not fail the delivery.
+async fn evaluate_and_dispatch_alerts(redis_client: &redis::Client, config: &Config, records: &[DmarcRecord]) {
+    if config.alert_webhook_urls.is_empty() {
+        return;
+    }
+
+    let mut conn = match redis_client.get_multiplexed_async_connection().await {
+        Ok(conn) => conn,
+        Err(e) => {
+            log::warn!("Alert evaluation skipped, failed to connect to Redis: {:?}", e);
+            return;
+        }
+    };
+    let known_source_ips: HashSet<String> = match conn.smembers(REDIS_KEY_KNOWN_SOURCE_IPS).await {
+        Ok(ips) => ips,
+        Err(e) => {
+            log::warn!("Alert evaluation skipped, failed to read known source IPs from Redis: {:?}", e);
+            return;
+        }
+    };
+
+    let evaluator = AlertEvaluator::new(AlertRules::from_config(config));
+    if let Some(payload) = evaluator.evaluate(records, Some(&known_source_ips)) {
+        log::warn!("DMARC alert triggered: {:?}", payload.kinds);
+        match AlertDispatcher::new(
+            &config.alert_webhook_urls,
+            std::time::Duration::from_secs(config.alert_timeout_secs),
+            config.alert_max_retries,
+        ) {
+            Ok(dispatcher) => {
+                if let Err(e) = dispatcher.dispatch(&payload).await {
+                    log::warn!("Failed to dispatch alert: {:?}", e);
+                }
+            }
+            Err(e) => log::warn!("Failed to build alert dispatcher: {:?}", e),
+        }
+    }
+
+    let distinct_ips: Vec<&str> = records.iter().map(|r| r.source_ip.as_str()).collect();
+    if !distinct_ips.is_empty() {
+        if let Err(e) = conn.sadd::<_, _, ()>(REDIS_KEY_KNOWN_SOURCE_IPS, distinct_ips).await {
+            log::warn!("Failed to persist known source IPs to Redis: {:?}", e);
+        }
+    }
+}
+
+/// Everything a delivery handler needs, cloned cheaply into each spawned task.
+#[derive(Clone)]
+struct WorkerContext {
+    channel: Channel,
+    pg_pool: PgPool,
+    redis_client: redis::Client,
+    shared_config: SharedConfig,
+}
+
+/// Reads the `x-retry-count` header off a delivery, defaulting to 0 for first attempts.
+fn retry_count(properties: &BasicProperties) -> i32 {
+    properties
+        .headers()
+        .as_ref()
+        .and_then(|headers| headers.inner().get(HEADER_RETRY_COUNT))
+        .and_then(|value| match value {
+            AMQPValue::LongInt(n) => Some(*n),
+            AMQPValue::ShortInt(n) => Some(*n as i32),
+            _ => None,
+        })
+        .unwrap_or(0)
+}
+
+/// Republishes `data` onto the retry queue with an incremented `x-retry-count` header
+/// and a per-message TTL (`expiration`) equal to the exponential backoff delay, so
+/// RabbitMQ dead-letters it back onto the main queue once the delay elapses.
+async fn requeue_with_backoff(channel: &Channel, data: &[u8], attempt: i32) -> Result<()> {
+    let delay_ms = RETRY_BASE_DELAY_MS.saturating_mul(1u64 << attempt.max(0).min(20) as u32);
+    let mut headers = FieldTable::default();
+    headers.insert(HEADER_RETRY_COUNT.into(), AMQPValue::LongInt(attempt + 1));
+    let properties = BasicProperties::default()
+        .with_headers(headers)
+        .with_expiration(ShortString::from(delay_ms.to_string()));
+    channel
+        .basic_publish("", QUEUE_RETRY, BasicPublishOptions::default(), data, properties)
+        .await?
+        .await?;
+    Ok(())
+}
+
+/// Removes the report file `api.rs::ingest_report` staged under
+/// `std::env::temp_dir()` before publishing this delivery's `TaskMessage`. Called
+/// once a delivery reaches a terminal outcome (acked or permanently/exhaustedly
+/// nacked) so a report submitted through `POST /reports` doesn't leave its staged
+/// upload on disk forever; a delivery still queued for retry keeps its file, since
+/// `download_file` will read it again from the same path. A path whose file name
+/// lacks the API's `dmarcer-upload-` prefix is left untouched.
+async fn cleanup_staged_file(file_path: &str) {
+    let is_api_upload = std::path::Path::new(file_path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map_or(false, |name| name.starts_with("dmarcer-upload-"));
+    if !is_api_upload {
+        return;
+    }
+    if let Err(e) = tokio::fs::remove_file(file_path).await {
+        eprintln!("Failed to remove staged report file {}: {:?}", file_path, e);
+    }
+}
+
+/// Processes a single delivery end to end, isolated from the rest of the consumer loop
+/// so one bad report or DB hiccup can't take the whole worker down.
+async fn handle_delivery(ctx: WorkerContext, delivery: lapin::message::Delivery) {
+    let attempt = retry_count(&delivery.properties);
+
+    let task_msg: std::result::Result<TaskMessage, serde_json::Error> = serde_json::from_slice(&delivery.data);
+    let staged_file_path = task_msg.as_ref().ok().map(|m| m.file_path.clone());
+
+    let outcome: Result<(), Failure> = async {
+        let task_msg = task_msg.map_err(|e| Failure::Permanent(e.into()))?;
+        println!("Processing task: {} (attempt {})", task_msg.task_id, attempt + 1);
+
+        let file_path = download_file(&task_msg.file_path).await.map_err(classify_error)?;
+        let records = process_report(&file_path, ctx.shared_config.load_full())
+            .await
+            .map_err(classify_error)?;
+        insert_records_to_postgres(&ctx.pg_pool, &task_msg.task_id, &records)
+            .await
+            .map_err(classify_error)?;
+        publish_notification(&ctx.redis_client, &task_msg.task_id)
+            .await
+            .map_err(classify_error)?;
+        evaluate_and_dispatch_alerts(&ctx.redis_client, &ctx.shared_config.load_full(), &records).await;
+        Ok(())
+    }
+    .await;
+
+    match outcome {
+        Ok(()) => {
+            if let Err(e) = delivery.ack(BasicAckOptions::default()).await {
+                eprintln!("Failed to ack delivery: {:?}", e);
+            }
+            if let Some(file_path) = &staged_file_path {
+                cleanup_staged_file(file_path).await;
+            }
+        }
+        Err(Failure::Permanent(e)) => {
+            eprintln!("Permanent failure, routing to dead-letter queue: {:?}", e);
+            if let Err(e) = delivery.nack(BasicNackOptions { requeue: false, ..Default::default() }).await {
+                eprintln!("Failed to nack delivery: {:?}", e);
+            }
+            if let Some(file_path) = &staged_file_path {
+                cleanup_staged_file(file_path).await;
+            }
+        }
+        Err(Failure::Transient(e)) if attempt < MAX_RETRIES => {
+            eprintln!("Transient failure (attempt {}), scheduling retry: {:?}", attempt + 1, e);
+            if let Err(requeue_err) = requeue_with_backoff(&ctx.channel, &delivery.data, attempt).await {
+                eprintln!("Failed to schedule retry, dead-lettering instead: {:?}", requeue_err);
+                let _ = delivery.nack(BasicNackOptions { requeue: false, ..Default::default() }).await;
+                if let Some(file_path) = &staged_file_path {
+                    cleanup_staged_file(file_path).await;
+                }
+                return;
+            }
+            if let Err(e) = delivery.ack(BasicAckOptions::default()).await {
+                eprintln!("Failed to ack delivery after scheduling retry: {:?}", e);
+            }
+            // Retry scheduled: leave the staged file in place for the redelivered attempt.
+        }
+        Err(Failure::Transient(e)) => {
+            eprintln!("Transient failure exhausted {} retries, routing to dead-letter queue: {:?}", MAX_RETRIES, e);
+            if let Err(e) = delivery.nack(BasicNackOptions { requeue: false, ..Default::default() }).await {
+                eprintln!("Failed to nack delivery: {:?}", e);
+            }
+            if let Some(file_path) = &staged_file_path {
+                cleanup_staged_file(file_path).await;
+            }
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Load configuration from environment variables.
@@ -111,16 +415,37 @@ async fn main() -> Result<()> {
     // Connect to RabbitMQ.
     let conn = Connection::connect(&amqp_addr, ConnectionProperties::default()).await?;
     let channel = conn.create_channel().await?;
+
+    // The dead-letter queue simply holds messages that exhausted their retries, for
+    // manual inspection; it has no further dead-lettering of its own.
     channel
-        .queue_declare(
-            "dmarc_processing",
-            QueueDeclareOptions::default(),
-            FieldTable::default(),
-        )
+        .queue_declare(QUEUE_DEAD, QueueDeclareOptions::default(), FieldTable::default())
         .await?;
+
+    // The retry queue holds a message for its TTL (set per-publish in
+    // `requeue_with_backoff`), then the broker dead-letters it back onto the main
+    // queue via the default exchange, giving a delayed-retry effect without a timer
+    // task of our own.
+    let mut retry_args = FieldTable::default();
+    retry_args.insert("x-dead-letter-exchange".into(), AMQPValue::LongString("".into()));
+    retry_args.insert("x-dead-letter-routing-key".into(), AMQPValue::LongString(QUEUE_MAIN.into()));
+    channel
+        .queue_declare(QUEUE_RETRY, QueueDeclareOptions::default(), retry_args)
+        .await?;
+
+    // The main queue dead-letters straight to the dead-letter queue whenever we
+    // `nack` with `requeue: false`, so permanent failures and exhausted retries
+    // land there without any extra publish on our part.
+    let mut main_args = FieldTable::default();
+    main_args.insert("x-dead-letter-exchange".into(), AMQPValue::LongString("".into()));
+    main_args.insert("x-dead-letter-routing-key".into(), AMQPValue::LongString(QUEUE_DEAD.into()));
+    channel
+        .queue_declare(QUEUE_MAIN, QueueDeclareOptions::default(), main_args)
+        .await?;
+
     let mut consumer = channel
         .basic_consume(
-            "dmarc_processing",
+            QUEUE_MAIN,
             "dmarc_consumer",
             BasicConsumeOptions::default(),
             FieldTable::default(),
@@ -133,30 +458,27 @@ async fn main() -> Result<()> {
     // Set up Redis client.
     let redis_client = redis::Client::open(redis_url)?;
 
+    // Load the initial configuration and install the SIGHUP hot-reload handler.
+    let config_file = env::var("DMARC_CONFIG_FILE").ok();
+    let shared_config: SharedConfig = Arc::new(ArcSwap::from_pointee(Config::reload(config_file.as_deref())?));
+    spawn_config_reload_task(shared_config.clone(), config_file);
+
+    let ctx = WorkerContext {
+        channel: channel.clone(),
+        pg_pool,
+        redis_client,
+        shared_config,
+    };
+
     println!("Worker started, waiting for messages...");
 
-    // Process messages continuously.
+    // Process messages continuously. Each delivery is handed off to its own task so
+    // a single slow or stuck report can't stall the rest of the queue; `handle_delivery`
+    // takes care of acking, nacking, and retry/dead-lettering on its own.
     while let Some(delivery_result) = consumer.next().await {
         match delivery_result {
             Ok(delivery) => {
-                // Deserialize the task message.
-                let task_msg: TaskMessage = serde_json::from_slice(&delivery.data)?;
-                println!("Processing task: {}", task_msg.task_id);
-
-                // Download the DMARC report file.
-                let file_content = download_file(&task_msg.file_path).await?;
-
-                // Process the report.
-                let records = process_report(&file_content).await?;
-
-                // Insert records into PostgreSQL.
-                insert_records_to_postgres(&pg_pool, &task_msg.task_id, &records).await?;
-
-                // Publish a notification.
-                publish_notification(&redis_client, &task_msg.task_id).await?;
-
-                // Acknowledge the message.
-                delivery.ack(BasicAckOptions::default()).await?;
+                tokio::spawn(handle_delivery(ctx.clone(), delivery));
             }
             Err(e) => {
                 eprintln!("Error receiving message: {:?}", e);
@@ -7,14 +7,16 @@
 //! This ensures that DMARC reports (which do not require DTD processing) are parsed safely.
 
 use crate::error::{DmarcError, Result};
-use crate::models::{DmarcRecord, DmarcPolicy, DkimResult, SpfResult, DateRange};
+use crate::models::{DmarcRecord, DmarcPolicy, DkimResult, SpfResult, DateRange, ReportMetadata, PolicyOverrideReason};
 use crate::models::{DkimVerdict, SpfVerdict, AlignmentMode, PolicyType};
 use quick_xml::events::Event;
 use quick_xml::Reader;
 use regex::Regex;
 use std::io;
 
-pub fn parse_dmarc_xml(xml_content: &str) -> Result<(Vec<DmarcRecord>, DmarcPolicy)> {
+/// Parses a DMARC aggregate report, returning the records, the published policy,
+/// and the report metadata (who sent it, its ID, and the reporting window).
+pub fn parse_dmarc_xml(xml_content: &str) -> Result<(Vec<DmarcRecord>, DmarcPolicy, ReportMetadata)> {
     // Use a regex to locate the DOCTYPE block.
     let re = Regex::new(r"(?s)<!DOCTYPE.*?\]>").unwrap();
     let cleaned_xml = if let Some(mat) = re.find(xml_content) {
@@ -40,11 +42,17 @@ pub fn parse_dmarc_xml(xml_content: &str) -> Result<(Vec<DmarcRecord>, DmarcPoli
         adkim: AlignmentMode::Relaxed,
         aspf: AlignmentMode::Relaxed,
         policy: PolicyType::None,
+        sp: PolicyType::None,
+        fo: String::new(),
+        rf: String::new(),
+        ri: 86400,
         pct: 100,
     };
+    let mut metadata = ReportMetadata::default();
 
     let mut current_record: Option<DmarcRecord> = None;
     let mut in_auth_results = false;
+    let mut in_identifiers = false;
     let mut depth: u32 = 0;
     let max_depth = 100; // Increased depth limit to allow valid DMARC reports
 
@@ -58,12 +66,16 @@ pub fn parse_dmarc_xml(xml_content: &str) -> Result<(Vec<DmarcRecord>, DmarcPoli
                     )));
                 }
                 match e.name().as_ref() {
+                    b"report_metadata" => {
+                        metadata = parse_report_metadata(&mut reader)?;
+                    }
                     b"record" => {
                         current_record = Some(DmarcRecord {
                             source_ip: String::new(),
                             count: 0,
                             header_from: String::new(),
                             envelope_from: None,
+                            envelope_to: None,
                             policy_evaluated: Default::default(),
                             dkim: Vec::new(),
                             spf: SpfResult {
@@ -80,6 +92,14 @@ pub fn parse_dmarc_xml(xml_content: &str) -> Result<(Vec<DmarcRecord>, DmarcPoli
                     b"auth_results" => {
                         in_auth_results = true;
                     }
+                    b"identifiers" => {
+                        in_identifiers = true;
+                    }
+                    b"policy_evaluated" => {
+                        if let Some(record) = current_record.as_mut() {
+                            record.policy_evaluated = parse_policy_evaluated(&mut reader)?;
+                        }
+                    }
                     b"source_ip" => {
                         if let Some(record) = current_record.as_mut() {
                             record.source_ip = reader.read_text(e.name())?.trim().to_string();
@@ -90,11 +110,23 @@ pub fn parse_dmarc_xml(xml_content: &str) -> Result<(Vec<DmarcRecord>, DmarcPoli
                             record.count = reader.read_text(e.name())?.trim().parse().unwrap_or(0);
                         }
                     }
-                    b"header_from" => {
+                    b"header_from" if in_identifiers => {
                         if let Some(record) = current_record.as_mut() {
                             record.header_from = reader.read_text(e.name())?.trim().to_string();
                         }
                     }
+                    b"envelope_from" if in_identifiers => {
+                        if let Some(record) = current_record.as_mut() {
+                            let text = reader.read_text(e.name())?.trim().to_string();
+                            record.envelope_from = (!text.is_empty()).then_some(text);
+                        }
+                    }
+                    b"envelope_to" if in_identifiers => {
+                        if let Some(record) = current_record.as_mut() {
+                            let text = reader.read_text(e.name())?.trim().to_string();
+                            record.envelope_to = (!text.is_empty()).then_some(text);
+                        }
+                    }
                     b"dkim" => {
                         if in_auth_results {
                             if let Some(record) = current_record.as_mut() {
@@ -123,6 +155,9 @@ pub fn parse_dmarc_xml(xml_content: &str) -> Result<(Vec<DmarcRecord>, DmarcPoli
                     b"auth_results" => {
                         in_auth_results = false;
                     }
+                    b"identifiers" => {
+                        in_identifiers = false;
+                    }
                     _ => {}
                 }
                 depth = depth.saturating_sub(1);
@@ -133,7 +168,127 @@ pub fn parse_dmarc_xml(xml_content: &str) -> Result<(Vec<DmarcRecord>, DmarcPoli
         }
     }
 
-    Ok((records, policy))
+    Ok((records, policy, metadata))
+}
+
+fn parse_report_metadata(reader: &mut Reader<&[u8]>) -> Result<ReportMetadata> {
+    let mut metadata = ReportMetadata::default();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(ref e)) => {
+                match e.name().as_ref() {
+                    b"org_name" => {
+                        metadata.org_name = reader.read_text(e.name())?.trim().to_string();
+                    }
+                    b"email" => {
+                        metadata.email = reader.read_text(e.name())?.trim().to_string();
+                    }
+                    b"extra_contact_info" => {
+                        let text = reader.read_text(e.name())?.trim().to_string();
+                        metadata.extra_contact_info = (!text.is_empty()).then_some(text);
+                    }
+                    b"report_id" => {
+                        metadata.report_id = reader.read_text(e.name())?.trim().to_string();
+                    }
+                    b"date_range" => {
+                        metadata.date_range = parse_date_range(reader, b"date_range")?;
+                    }
+                    b"error" => {
+                        metadata.errors.push(reader.read_text(e.name())?.trim().to_string());
+                    }
+                    _ => {}
+                }
+            }
+            Ok(Event::End(ref e)) => {
+                if e.name().as_ref() == b"report_metadata" {
+                    break;
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(DmarcError::Xml(e)),
+            _ => {}
+        }
+    }
+    Ok(metadata)
+}
+
+fn parse_date_range(reader: &mut Reader<&[u8]>, end_tag: &[u8]) -> Result<DateRange> {
+    let mut date_range = DateRange::default();
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(ref e)) => {
+                match e.name().as_ref() {
+                    b"begin" => {
+                        date_range.begin = reader.read_text(e.name())?.trim().parse().unwrap_or(0);
+                    }
+                    b"end" => {
+                        date_range.end = reader.read_text(e.name())?.trim().parse().unwrap_or(0);
+                    }
+                    _ => {}
+                }
+            }
+            Ok(Event::End(ref e)) => {
+                if e.name().as_ref() == end_tag {
+                    break;
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(DmarcError::Xml(e)),
+            _ => {}
+        }
+    }
+    Ok(date_range)
+}
+
+fn parse_policy_evaluated(reader: &mut Reader<&[u8]>) -> Result<crate::models::PolicyEvaluated> {
+    let mut evaluated = crate::models::PolicyEvaluated::default();
+    let mut reason_type: Option<String> = None;
+    let mut reason_comment: Option<String> = None;
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(ref e)) => {
+                match e.name().as_ref() {
+                    b"disposition" => {
+                        evaluated.disposition = reader.read_text(e.name())?.trim().to_string();
+                    }
+                    b"dkim" => {
+                        let text = reader.read_text(e.name())?.trim().to_string();
+                        evaluated.dkim = text.parse().unwrap_or(DkimVerdict::None);
+                    }
+                    b"spf" => {
+                        let text = reader.read_text(e.name())?.trim().to_string();
+                        evaluated.spf = text.parse().unwrap_or(SpfVerdict::None);
+                    }
+                    b"type" => {
+                        reason_type = Some(reader.read_text(e.name())?.trim().to_string());
+                    }
+                    b"comment" => {
+                        let text = reader.read_text(e.name())?.trim().to_string();
+                        reason_comment = (!text.is_empty()).then_some(text);
+                    }
+                    _ => {}
+                }
+            }
+            Ok(Event::End(ref e)) => {
+                match e.name().as_ref() {
+                    b"reason" => {
+                        if let Some(reason_type) = reason_type.take() {
+                            evaluated.reasons.push(PolicyOverrideReason {
+                                reason_type,
+                                comment: reason_comment.take(),
+                            });
+                        }
+                    }
+                    b"policy_evaluated" => break,
+                    _ => {}
+                }
+            }
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(DmarcError::Xml(e)),
+            _ => {}
+        }
+    }
+    Ok(evaluated)
 }
 
 fn parse_policy_published(reader: &mut Reader<&[u8]>) -> Result<DmarcPolicy> {
@@ -141,6 +296,10 @@ fn parse_policy_published(reader: &mut Reader<&[u8]>) -> Result<DmarcPolicy> {
     let mut adkim = AlignmentMode::Relaxed;
     let mut aspf = AlignmentMode::Relaxed;
     let mut p = PolicyType::None;
+    let mut sp: Option<PolicyType> = None;
+    let mut fo = String::new();
+    let mut rf = String::new();
+    let mut ri = 86400u32;
     let mut pct = 100u8;
     loop {
         match reader.read_event() {
@@ -165,6 +324,24 @@ fn parse_policy_published(reader: &mut Reader<&[u8]>) -> Result<DmarcPolicy> {
                             _ => PolicyType::None,
                         };
                     },
+                    b"sp" => {
+                        let text = reader.read_text(e.name())?.trim().to_string();
+                        sp = Some(match text.to_lowercase().as_str() {
+                            "reject" => PolicyType::Reject,
+                            "quarantine" => PolicyType::Quarantine,
+                            _ => PolicyType::None,
+                        });
+                    },
+                    b"fo" => {
+                        fo = reader.read_text(e.name())?.trim().to_string();
+                    },
+                    b"rf" => {
+                        rf = reader.read_text(e.name())?.trim().to_string();
+                    },
+                    b"ri" => {
+                        let text = reader.read_text(e.name())?.trim().to_string();
+                        ri = text.parse().unwrap_or(86400);
+                    },
                     b"pct" => {
                         let text = reader.read_text(e.name())?.trim().to_string();
                         pct = text.parse().unwrap_or(100);
@@ -186,6 +363,11 @@ fn parse_policy_published(reader: &mut Reader<&[u8]>) -> Result<DmarcPolicy> {
         domain,
         adkim,
         aspf,
+        // RFC 7489: `sp` defaults to the domain-level `p` when not published.
+        sp: sp.unwrap_or_else(|| p.clone()),
+        fo,
+        rf,
+        ri,
         policy: p,
         pct,
     })
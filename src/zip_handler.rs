@@ -1,17 +1,80 @@
 //! ZIP Handler Module
 //!
-//! This module handles extraction of DMARC report files from ZIP and GZIP archives.
-//! It enforces security measures including file size limits, maximum decompressed
-//! size, file count, compression ratio, filename length, and path traversal prevention.
-use std::fs::File;
-use std::io::{Read, BufReader};
+//! This module handles extraction of DMARC report files from ZIP, GZIP, BZIP2, XZ,
+//! Zstandard, and 7z archives. It enforces security measures including file size
+//! limits, maximum decompressed size, file count, compression ratio, filename
+//! length, and path traversal prevention.
+//!
+//! Format is decided by sniffing the leading magic bytes (sharing
+//! [`file_handlers::detect_format`](crate::file_handlers::detect_format) with
+//! `FileHandler`), falling back to the file extension only when sniffing is
+//! inconclusive (e.g. a plain `.xml` file has no magic bytes) — the same
+//! precedence `FileHandler::process_file` uses, so a misleadingly- or un-named
+//! DMARC attachment is routed the same way regardless of which pipeline it
+//! enters through.
+//!
+//! Extraction is async and streaming wherever an async decoder exists: each
+//! entry (or, for a bare compressed file, the whole file) is decompressed in
+//! bounded chunks rather than all at once, with a running decompressed-byte
+//! total and a per-entry compression ratio checked after every chunk. This
+//! bounds memory against a zip/compression bomb even when the archive's
+//! header-declared `size()` understates how large an entry really decompresses
+//! to. `.7z` has no async decoder available (`sevenz_rust` is sync-only), so it
+//! runs on a blocking task instead, reusing `FileHandler`'s bounded extraction.
 use std::path::Path;
 use anyhow::{Result, Context};
-use zip::ZipArchive;
-use flate2::read::GzDecoder;
+use async_zip::tokio::read::seek::ZipFileReader;
+use async_compression::tokio::bufread::{BzDecoder, GzipDecoder, XzDecoder, ZstdDecoder};
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, BufReader};
 use crate::error::DmarcError;
 use crate::config::Config;
-/// Extracts files from a ZIP, GZIP, or XML file.
+use crate::file_handlers::{self, DetectedFormat};
+
+/// Chunk size used when streaming decompressed bytes out of an entry.
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// How [`extract_zip`] will treat a file, resolved from magic-byte sniffing
+/// with an extension-based fallback. Distinct from `DetectedFormat` because
+/// XML has no magic bytes and is only ever reached via the extension fallback.
+enum ExtractKind {
+    Zip,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+    SevenZ,
+    Xml,
+}
+
+/// Sniffs `file_path`'s leading bytes and falls back to `ext` when sniffing is
+/// inconclusive, mirroring `FileHandler::process_file`'s precedence.
+async fn classify(file_path: &Path, ext: &str) -> Result<ExtractKind> {
+    let mut file = File::open(file_path).await.context("Failed to open file")?;
+    let mut header = [0u8; file_handlers::SNIFF_LEN];
+    let read = file.read(&mut header).await.map_err(DmarcError::Io)?;
+
+    Ok(match file_handlers::detect_format(&header[..read]) {
+        DetectedFormat::Zip => ExtractKind::Zip,
+        DetectedFormat::Gzip => ExtractKind::Gzip,
+        DetectedFormat::Bzip2 => ExtractKind::Bzip2,
+        DetectedFormat::Xz => ExtractKind::Xz,
+        DetectedFormat::Zstd => ExtractKind::Zstd,
+        DetectedFormat::SevenZ => ExtractKind::SevenZ,
+        DetectedFormat::Unknown => match ext {
+            "zip" => ExtractKind::Zip,
+            "gz" => ExtractKind::Gzip,
+            "bz2" => ExtractKind::Bzip2,
+            "xz" | "lzma" => ExtractKind::Xz,
+            "zst" => ExtractKind::Zstd,
+            "7z" => ExtractKind::SevenZ,
+            "xml" => ExtractKind::Xml,
+            _ => return Err(DmarcError::UnsupportedFile("Unsupported file type".into()).into()),
+        },
+    })
+}
+
+/// Extracts files from a ZIP, GZIP, BZIP2, XZ, Zstandard, 7z, or XML file.
 ///
 /// # Arguments
 ///
@@ -21,75 +84,171 @@ use crate::config::Config;
 /// # Security Checks
 ///
 /// - Verifies that the original file size does not exceed the maximum.
-/// - For ZIP archives: verifies the number of files, checks for path traversal, file name length,
-///   compression ratio, and decompressed size.
-/// - For GZIP and XML files: checks the decompressed content size.
-pub fn extract_zip<P: AsRef<Path>>(file_path: P, config: &Config) -> Result<Vec<String>> {
-    let file = File::open(&file_path).context("Failed to open file")?;
-    let file_size = file.metadata()?.len();
+/// - For ZIP archives: checks the entry count, path traversal, and filename length
+///   up front, before any decompression begins, then streams each entry, aborting
+///   the moment its running compression ratio or the archive-wide decompressed
+///   total crosses the configured limit (the header-declared `size()` is never
+///   trusted on its own).
+/// - For GZIP/BZIP2/XZ/Zstandard files: streams the single entry the same way,
+///   using the compressed file size as the ratio denominator.
+/// - For 7z archives: decompresses each entry on a blocking task via
+///   `FileHandler::extract_sevenz`, under the same decompressed-size and
+///   compression-ratio guards.
+/// - For XML files: the length of the content read is checked against `max_file_size`.
+pub async fn extract_zip<P: AsRef<Path>>(file_path: P, config: &Config) -> Result<Vec<String>> {
+    let file_path = file_path.as_ref();
+    let file_size = tokio::fs::metadata(file_path)
+        .await
+        .context("Failed to open file")?
+        .len();
     if file_size > config.max_file_size as u64 {
         return Err(DmarcError::FileTooLarge("File too large".to_string()).into());
     }
-    let file_name = file_path.as_ref()
+
+    let file_name = file_path
         .file_name()
         .map(|x| x.to_string_lossy().to_string())
         .unwrap_or_default();
     let ext = file_name.split('.').last().unwrap_or("").to_lowercase();
-    match ext.as_str() {
-        "zip" => {
-            let mut archive = ZipArchive::new(file)?;
-            if archive.len() > config.max_files_in_zip {
-                return Err(anyhow::anyhow!("Too many files in archive"));
-            }
-            let mut extracted = Vec::new();
-            for i in 0..archive.len() {
-                let mut file_in_zip = archive.by_index(i)?;
-                let inner_name = file_in_zip.name().to_string();
-                // Prevent path traversal
-                if inner_name.contains("..") || inner_name.starts_with('/') || inner_name.starts_with('\\') {
-                    return Err(DmarcError::Format(format!("Path traversal attempt detected: {}", inner_name)).into());
-                }
-                // Check filename length
-                if inner_name.len() > config.max_filename_length {
-                    return Err(DmarcError::Format("Filename too long".to_string()).into());
-                }
-                let compressed_size = file_in_zip.compressed_size();
-                let uncompressed_size = file_in_zip.size();
-                if compressed_size > 0 {
-                    let compression_ratio = uncompressed_size as f64 / compressed_size as f64;
-                    if compression_ratio > config.max_compression_ratio {
-                        return Err(DmarcError::Format(format!("Suspicious compression ratio: {:.2}", compression_ratio)).into());
-                    }
-                }
-                if uncompressed_size > config.max_decompressed_size as u64 {
-                    return Err(DmarcError::FileTooLarge("Total decompressed size too large".to_string()).into());
-                }
-                let mut contents = String::new();
-                file_in_zip.read_to_string(&mut contents)?;
-                extracted.push(contents);
-            }
-            Ok(extracted)
-        },
-        "gz" => {
-            let mut gz_decoder = GzDecoder::new(file);
-            let mut contents = String::new();
-            let len = gz_decoder.read_to_string(&mut contents)?;
-            if len > config.max_decompressed_size {
-                return Err(DmarcError::FileTooLarge("Decompressed size too large".to_string()).into());
-            }
+
+    match classify(file_path, &ext).await? {
+        ExtractKind::Zip => extract_zip_archive(file_path, config).await,
+        ExtractKind::Gzip => {
+            let file = File::open(file_path).await.context("Failed to open file")?;
+            let decoder = GzipDecoder::new(BufReader::new(file));
+            let (contents, _) = stream_decompress_entry(decoder, file_size, config, 0).await?;
             Ok(vec![contents])
-        },
-        "xml" => {
+        }
+        ExtractKind::Bzip2 => {
+            let file = File::open(file_path).await.context("Failed to open file")?;
+            let decoder = BzDecoder::new(BufReader::new(file));
+            let (contents, _) = stream_decompress_entry(decoder, file_size, config, 0).await?;
+            Ok(vec![contents])
+        }
+        ExtractKind::Xz => {
+            let file = File::open(file_path).await.context("Failed to open file")?;
+            let decoder = XzDecoder::new(BufReader::new(file));
+            let (contents, _) = stream_decompress_entry(decoder, file_size, config, 0).await?;
+            Ok(vec![contents])
+        }
+        ExtractKind::Zstd => {
+            let file = File::open(file_path).await.context("Failed to open file")?;
+            let decoder = ZstdDecoder::new(BufReader::new(file));
+            let (contents, _) = stream_decompress_entry(decoder, file_size, config, 0).await?;
+            Ok(vec![contents])
+        }
+        ExtractKind::SevenZ => {
+            let path_buf = file_path.to_path_buf();
+            let handler_config = config.clone();
+            tokio::task::spawn_blocking(move || {
+                crate::file_handlers::FileHandler::new(handler_config).extract_sevenz(&path_buf)
+            })
+            .await
+            .map_err(|e| DmarcError::Format(format!("7z extraction task panicked: {}", e)))?
+            .map_err(anyhow::Error::from)
+        }
+        ExtractKind::Xml => {
+            let file = File::open(file_path).await.context("Failed to open file")?;
             let mut reader = BufReader::new(file);
             let mut contents = String::new();
-            let len = reader.read_to_string(&mut contents)?;
+            let len = reader.read_to_string(&mut contents).await.map_err(DmarcError::Io)?;
             if len as u64 > config.max_file_size as u64 {
                 return Err(DmarcError::FileTooLarge("XML file size too large".to_string()).into());
             }
             Ok(vec![contents])
-        },
-        _ => {
-            Err(DmarcError::UnsupportedFile("Unsupported file type".into()).into())
         }
     }
 }
+
+/// Opens `file_path` as a ZIP archive and streams every entry through
+/// [`stream_decompress_entry`], maintaining a running decompressed-byte total
+/// across the whole archive.
+async fn extract_zip_archive(file_path: &Path, config: &Config) -> Result<Vec<String>> {
+    let file = File::open(file_path).await.context("Failed to open file")?;
+    let mut reader = ZipFileReader::with_tokio(file)
+        .await
+        .map_err(|e| DmarcError::Format(format!("Invalid ZIP archive: {}", e)))?;
+
+    let entry_count = reader.file().entries().len();
+    if entry_count > config.max_files_in_zip {
+        return Err(DmarcError::Format("Too many files in archive".to_string()).into());
+    }
+
+    // Path traversal and filename-length checks happen up front, against every
+    // entry's metadata, before a single byte of any entry is decompressed.
+    for entry in reader.file().entries() {
+        let inner_name = entry
+            .filename()
+            .as_str()
+            .map_err(|e| DmarcError::Format(format!("Invalid entry filename: {}", e)))?
+            .to_string();
+        if inner_name.contains("..") || inner_name.starts_with('/') || inner_name.starts_with('\\') {
+            return Err(DmarcError::Format(format!("Path traversal attempt detected: {}", inner_name)).into());
+        }
+        if inner_name.len() > config.max_filename_length {
+            return Err(DmarcError::Format("Filename too long".to_string()).into());
+        }
+    }
+
+    let mut extracted = Vec::new();
+    let mut total_decompressed: u64 = 0;
+    for index in 0..entry_count {
+        let compressed_size = reader.file().entries()[index].compressed_size();
+        let entry_reader = reader
+            .reader_with_entry(index)
+            .await
+            .map_err(|e| DmarcError::Format(format!("Failed to open archive entry: {}", e)))?;
+
+        let (contents, entry_total) =
+            stream_decompress_entry(entry_reader, compressed_size, config, total_decompressed).await?;
+        total_decompressed += entry_total;
+        extracted.push(contents);
+    }
+
+    Ok(extracted)
+}
+
+/// Streams `reader` in [`STREAM_CHUNK_SIZE`] chunks, aborting the moment either:
+///
+/// - the per-entry compression ratio, `bytes decompressed so far / compressed_size`,
+///   exceeds `config.max_compression_ratio`; or
+/// - the archive-wide decompressed total, `running_total` plus this entry's bytes
+///   so far, exceeds `config.max_decompressed_size`.
+///
+/// Neither check trusts a header-declared size; both are evaluated against bytes
+/// actually produced by the decoder. Returns the entry's text and the number of
+/// bytes it decompressed to, so the caller can fold that into its running total.
+async fn stream_decompress_entry<R: AsyncRead + Unpin>(
+    mut reader: R,
+    compressed_size: u64,
+    config: &Config,
+    running_total: u64,
+) -> Result<(String, u64)> {
+    let mut buf = vec![0u8; STREAM_CHUNK_SIZE];
+    let mut contents = Vec::new();
+    let mut entry_total: u64 = 0;
+
+    loop {
+        let n = reader.read(&mut buf).await.map_err(DmarcError::Io)?;
+        if n == 0 {
+            break;
+        }
+        entry_total += n as u64;
+
+        if compressed_size > 0 {
+            let ratio = entry_total as f64 / compressed_size as f64;
+            if ratio > config.max_compression_ratio {
+                return Err(DmarcError::Format(format!("Suspicious compression ratio: {:.2}", ratio)).into());
+            }
+        }
+        if running_total + entry_total > config.max_decompressed_size as u64 {
+            return Err(DmarcError::FileTooLarge("Total decompressed size too large".to_string()).into());
+        }
+
+        contents.extend_from_slice(&buf[..n]);
+    }
+
+    let text = String::from_utf8(contents)
+        .map_err(|_| DmarcError::Parse("Invalid UTF-8 in decompressed ZIP entry".into()))?;
+    Ok((text, entry_total))
+}
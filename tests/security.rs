@@ -18,8 +18,8 @@ mod tests {
     const MAX_PROCESSING_TIME_MS: u128 = 2000; // 2 seconds for test
     const TEST_BOMB_SIZE: usize = 2 * 1024 * 1024; // 2MB bomb for test
     /// Test protection against a ZIP bomb attack.
-    #[test]
-    fn test_zip_bomb_protection() -> Result<()> {
+    #[tokio::test]
+    async fn test_zip_bomb_protection() -> Result<()> {
         let dir = tempdir()?;
         let zip_path = dir.path().join("zipbomb.zip");
         let file = File::create(&zip_path)?;
@@ -34,7 +34,7 @@ mod tests {
         let mut config = Config::new()?;
         config.max_decompressed_size = 1 * 1024 * 1024; // 1MB
         let start = Instant::now();
-        let result = extract_zip(&zip_path, &config);
+        let result = extract_zip(&zip_path, &config).await;
         let duration = start.elapsed();
         debug_assert!(
             duration.as_millis() < MAX_PROCESSING_TIME_MS,
@@ -63,13 +63,15 @@ mod tests {
             <record>
                 <source_ip>1.2.3.4</source_ip>
                 <count>1</count>
-                <header_from>example.com</header_from>
+                <identifiers>
+                    <header_from>example.com</header_from>
+                </identifiers>
             </record>
         </feedback>
         "#;
         let result = parse_dmarc_xml(xml);
         assert!(result.is_ok(), "Parser should handle malicious XML safely");
-        let (records, _) = result.unwrap();
+        let (records, _, _) = result.unwrap();
         for record in records {
             assert!(
                 !record.source_ip.contains("/etc/passwd"),
@@ -78,8 +80,8 @@ mod tests {
         }
     }
     /// Test protection against directory traversal in ZIP file entries.
-    #[test]
-    fn test_directory_traversal_protection() -> Result<()> {
+    #[tokio::test]
+    async fn test_directory_traversal_protection() -> Result<()> {
         let dir = tempdir()?;
         let zip_path = dir.path().join("traversal.zip");
         let file = File::create(&zip_path)?;
@@ -89,7 +91,7 @@ mod tests {
         zip.write_all(b"fake passwd file")?;
         zip.finish()?;
         let config = Config::new()?;
-        let result = extract_zip(&zip_path, &config);
+        let result = extract_zip(&zip_path, &config).await;
         assert!(result.is_err(), "Should block directory traversal attempt");
         Ok(())
     }
@@ -113,7 +115,9 @@ mod tests {
             <record>
                 <source_ip>1.2.3.4</source_ip>
                 <count>1</count>
-                <header_from>example.com</header_from>
+                <identifiers>
+                    <header_from>example.com</header_from>
+                </identifiers>
             </record>
         </feedback>
         "#;